@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+use image::RgbaImage;
+
+/// Every texture that can be packed into the atlas, by name.
+const TEXTURE_FILES: &[(&str, &[u8])] = &[
+    ("noise_128", include_bytes!("../textures/noise_128.png")),
+    ("dirt", include_bytes!("../textures/materials/dirt.png")),
+    ("grass_top", include_bytes!("../textures/materials/grass_top.png")),
+    ("grass_side", include_bytes!("../textures/materials/grass_side.png")),
+    ("stone", include_bytes!("../textures/materials/stone.png")),
+    ("sand", include_bytes!("../textures/materials/sand.png")),
+    ("water", include_bytes!("../textures/materials/water.png")),
+    ("lamp", include_bytes!("../textures/materials/lamp.png")),
+];
+
+/// Each tile's edge pixels are repeated this many times around it, so that sampling at the edge of
+/// a tile never picks up its neighbour.
+const PADDING: u32 = 2;
+
+/// Where a tile is in the atlas, in texture coordinates. Doesn't include the padding.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl AtlasRect {
+    /// The whole texture, for things that don't use the atlas.
+    pub const FULL: AtlasRect = AtlasRect { min: [0.0, 0.0], max: [1.0, 1.0] };
+
+    /// As stored in `Vertex::atlas_rect`.
+    pub fn to_array(self) -> [f32; 4] {
+        [self.min[0], self.min[1], self.max[0], self.max[1]]
+    }
+}
+
+pub struct TextureAtlas {
+    pub image: RgbaImage,
+    rects: HashMap<&'static str, AtlasRect>,
+}
+
+impl TextureAtlas {
+    pub fn new() -> Self {
+        let textures = TEXTURE_FILES.iter()
+            .map(|&(name, bytes)| (name, image::load_from_memory(bytes).unwrap().to_rgba8()))
+            .collect();
+        Self::pack(textures)
+    }
+
+    // Shelf packing: tiles go left to right in rows, tallest first, into a power-of-two square-ish
+    // image.
+    fn pack(mut textures: Vec<(&'static str, RgbaImage)>) -> Self {
+        textures.sort_by_key(|(name, texture)| (std::cmp::Reverse(texture.height()), *name));
+        let padded = |texture: &RgbaImage| (texture.width() + 2 * PADDING, texture.height() + 2 * PADDING);
+        let area: u32 = textures.iter().map(|(_, texture)| padded(texture).0 * padded(texture).1).sum();
+        let widest = textures.iter().map(|(_, texture)| padded(texture).0).max().unwrap_or(1);
+        let width = widest.max(area.isqrt()).next_power_of_two();
+
+        let mut positions = vec![];
+        let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+        for (_, texture) in &textures {
+            let (w, h) = padded(texture);
+            if x + w > width {
+                (x, y) = (0, y + shelf_height);
+                shelf_height = 0;
+            }
+            positions.push((x, y));
+            x += w;
+            shelf_height = shelf_height.max(h);
+        }
+        let height = (y + shelf_height).next_power_of_two();
+
+        let mut image = RgbaImage::new(width, height);
+        let mut rects = HashMap::new();
+        for ((name, texture), (x, y)) in textures.iter().zip(positions) {
+            let (w, h) = padded(texture);
+            for j in 0..h {
+                for i in 0..w {
+                    // Clamping the source coordinate repeats the edge into the padding.
+                    let source_x = i.saturating_sub(PADDING).min(texture.width() - 1);
+                    let source_y = j.saturating_sub(PADDING).min(texture.height() - 1);
+                    image.put_pixel(x + i, y + j, *texture.get_pixel(source_x, source_y));
+                }
+            }
+            let (x, y) = (x + PADDING, y + PADDING);
+            rects.insert(*name, AtlasRect {
+                min: [x as f32 / width as f32, y as f32 / height as f32],
+                max: [(x + texture.width()) as f32 / width as f32, (y + texture.height()) as f32 / height as f32],
+            });
+        }
+        TextureAtlas { image, rects }
+    }
+
+    /// Panics if there's no texture with that name.
+    pub fn get(&self, name: &str) -> AtlasRect {
+        match self.rects.get(name) {
+            Some(&rect) => rect,
+            None => panic!("No texture named {:?} in the atlas", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::material::MATERIALS;
+
+    fn solid_texture(size: u32, value: u8) -> RgbaImage {
+        RgbaImage::from_pixel(size, size, image::Rgba([value, value, value, 255]))
+    }
+
+    #[test]
+    fn tiles_do_not_overlap() {
+        let atlas = TextureAtlas::new();
+        let rects: Vec<_> = atlas.rects.values().collect();
+        for (n, a) in rects.iter().enumerate() {
+            assert!(a.min[0] >= 0.0 && a.min[1] >= 0.0 && a.max[0] <= 1.0 && a.max[1] <= 1.0);
+            for b in &rects[n + 1..] {
+                let separate = (0..2).any(|axis| a.max[axis] <= b.min[axis] || b.max[axis] <= a.min[axis]);
+                assert!(separate, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn padding_repeats_edges() {
+        let atlas = TextureAtlas::pack(vec![("a", solid_texture(4, 10)), ("b", solid_texture(4, 200)), ("c", solid_texture(8, 90))]);
+        for (name, value) in [("a", 10), ("b", 200), ("c", 90)] {
+            let rect = atlas.get(name);
+            let (width, height) = (atlas.image.width() as f32, atlas.image.height() as f32);
+            let min = ((rect.min[0] * width) as u32, (rect.min[1] * height) as u32);
+            let max = ((rect.max[0] * width) as u32, (rect.max[1] * height) as u32);
+            for y in min.1 - PADDING..max.1 + PADDING {
+                for x in min.0 - PADDING..max.0 + PADDING {
+                    assert_eq!(value, atlas.image.get_pixel(x, y)[0], "{} at {}, {}", name, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn has_every_material_texture() {
+        let atlas = TextureAtlas::new();
+        for material in MATERIALS.iter().filter(|m| m.is_visible) {
+            for name in [material.textures.top, material.textures.side, material.textures.bottom] {
+                atlas.get(name);
+            }
+        }
+    }
+}
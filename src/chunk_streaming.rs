@@ -0,0 +1,664 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use cgmath::{Vector3, vec3};
+
+use crate::atlas::TextureAtlas;
+use crate::lighting::light_chunk;
+use crate::material::MATERIALS;
+use crate::terrain::{FlowerSeed, GeneratedChunk, TerrainGenerator};
+use crate::voxel::{CHUNK_SIZE, ChunkMesh, FACE_DIRECTIONS, NeighborChunks, VoxelChunk};
+use crate::voxel_world::{VoxelWorld, voxel_to_chunk};
+
+const CHUNK_FILE_MAGIC: &[u8; 4] = b"HNKC";
+const CHUNK_FILE_VERSION: u32 = 1;
+
+pub struct StreamingConfig {
+    /// How far from the centre chunk, in chunks, to load chunks horizontally.
+    pub load_radius: i32,
+    /// How far above and below the centre chunk, in chunks, to load chunks.
+    pub vertical_radius: i32,
+    pub worker_count: usize,
+    /// At most this many chunks are loading or meshing at once, so a frame never has more results
+    /// to take in than that.
+    pub max_jobs_in_flight: usize,
+    /// At most this many chunks are added to the world each update. Each is lit against its
+    /// neighbours with `light_new_chunk` on the main thread, so this bounds how long that takes.
+    /// Loaded chunks past the limit wait for the next update.
+    pub max_chunks_added_per_update: usize,
+    /// Where modified chunks are written when they're evicted. If `None` they're only kept in
+    /// memory.
+    pub save_dir: Option<PathBuf>,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        StreamingConfig {
+            load_radius: 6,
+            vertical_radius: 2,
+            worker_count: thread::available_parallelism().map_or(1, |n| n.get().saturating_sub(1).max(1)),
+            max_jobs_in_flight: 16,
+            max_chunks_added_per_update: 2,
+            save_dir: None,
+        }
+    }
+}
+
+enum Job {
+    Load {
+        chunk_coord: Vector3<i32>,
+        // A modified chunk that was evicted, which comes back as it was rather than from the save
+        // directory or the generator.
+        evicted: Option<VoxelChunk>,
+    },
+    Mesh {
+        chunk_coord: Vector3<i32>,
+        chunk: VoxelChunk,
+        // In the order of `FACE_DIRECTIONS`.
+        neighbors: Box<[Option<VoxelChunk>; 6]>,
+    },
+    Save {
+        chunk_coord: Vector3<i32>,
+        chunk: VoxelChunk,
+    },
+}
+
+enum JobResult {
+    Loaded {
+        chunk_coord: Vector3<i32>,
+        chunk: VoxelChunk,
+        flowers: Vec<FlowerSeed>,
+    },
+    Meshed {
+        chunk_coord: Vector3<i32>,
+        mesh: ChunkMesh,
+        version: u64,
+    },
+    Saved {
+        chunk_coord: Vector3<i32>,
+        version: u64,
+    },
+}
+
+fn chunk_path(save_dir: &Path, chunk_coord: Vector3<i32>) -> PathBuf {
+    save_dir.join(format!("{}_{}_{}.chunk", chunk_coord.x, chunk_coord.y, chunk_coord.z))
+}
+
+fn chunk_coords() -> impl Iterator<Item = Vector3<usize>> {
+    (0..CHUNK_SIZE.z).flat_map(|k| (0..CHUNK_SIZE.y).flat_map(move |j| (0..CHUNK_SIZE.x).map(move |i| vec3(i, j, k))))
+}
+
+/// Writes the chunk's voxels. Light and meshes aren't saved, they're rebuilt after loading.
+fn write_chunk(path: &Path, chunk: &VoxelChunk) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(8 + 4 * CHUNK_SIZE.x * CHUNK_SIZE.y * CHUNK_SIZE.z);
+    bytes.extend_from_slice(CHUNK_FILE_MAGIC);
+    bytes.extend_from_slice(&CHUNK_FILE_VERSION.to_le_bytes());
+    for coord in chunk_coords() {
+        bytes.extend_from_slice(&chunk.get_voxel(coord).to_le_bytes());
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Written next to the file first so a crash can't leave half a chunk behind. Each version
+    // gets its own temporary file, so saves of different versions never write to the same one.
+    let temp_path = path.with_extension(format!("{}.tmp", chunk.version()));
+    fs::write(&temp_path, bytes)?;
+    fs::rename(temp_path, path)
+}
+
+/// `None` if the chunk was never saved.
+fn read_chunk(path: &Path) -> io::Result<Option<VoxelChunk>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let n_voxels = CHUNK_SIZE.x * CHUNK_SIZE.y * CHUNK_SIZE.z;
+    if bytes.len() != 8 + 4 * n_voxels || &bytes[0..4] != CHUNK_FILE_MAGIC || bytes[4..8] != CHUNK_FILE_VERSION.to_le_bytes() {
+        return Err(io::Error::new(ErrorKind::InvalidData, "not a chunk file"));
+    }
+    let mut chunk = VoxelChunk::new();
+    for (coord, voxel) in chunk_coords().zip(bytes[8..].chunks_exact(4)) {
+        let voxel = u32::from_le_bytes(voxel.try_into().unwrap());
+        if voxel as usize >= MATERIALS.len() {
+            return Err(io::Error::new(ErrorKind::InvalidData, format!("unknown voxel type {voxel}")));
+        }
+        chunk.set_voxel(coord, voxel);
+    }
+    chunk.clear_modified();
+    Ok(Some(chunk))
+}
+
+/// A chunk that was added to the world, lit on its own with `light_chunk`.
+pub struct LoadedChunk {
+    pub chunk_coord: Vector3<i32>,
+    /// The flowers the chunk starts with, if it was just generated rather than loaded from a save.
+    pub flowers: Vec<FlowerSeed>,
+}
+
+// Reads the chunk from the save directory, or generates it if it was never saved or can't be read.
+fn load_or_generate(generator: &dyn TerrainGenerator, save_dir: Option<&Path>, chunk_coord: Vector3<i32>) -> GeneratedChunk {
+    let saved = match save_dir.map(|dir| read_chunk(&chunk_path(dir, chunk_coord))) {
+        Some(Ok(chunk)) => chunk,
+        Some(Err(error)) => {
+            log::warn!("Couldn't load chunk {chunk_coord:?}, generating it again: {error}");
+            None
+        }
+        None => None,
+    };
+    let mut generated = match saved {
+        Some(chunk) => GeneratedChunk { chunk, flowers: vec![] },
+        None => generator.generate(chunk_coord),
+    };
+    generated.chunk.clear_modified();
+    generated
+}
+
+fn run_worker(jobs: Arc<Mutex<Receiver<Job>>>, results: Sender<JobResult>, generator: Arc<dyn TerrainGenerator>, atlas: Arc<TextureAtlas>, save_dir: Option<PathBuf>) {
+    loop {
+        // Only hold the lock while waiting for a job, so the other workers can take the next one.
+        let Ok(job) = jobs.lock().unwrap().recv() else {
+            return;
+        };
+        let result = match job {
+            Job::Load { chunk_coord, evicted } => {
+                // Evicted chunks keep their edits, so they stay modified. Their light is from
+                // neighbours that may have changed since, so it starts again.
+                let GeneratedChunk { mut chunk, flowers } = match evicted {
+                    Some(chunk) => GeneratedChunk { chunk, flowers: vec![] },
+                    None => load_or_generate(generator.as_ref(), save_dir.as_deref(), chunk_coord),
+                };
+                light_chunk(&mut chunk);
+                JobResult::Loaded { chunk_coord, chunk, flowers }
+            }
+            Job::Mesh { chunk_coord, chunk, neighbors } => {
+                let neighbor_chunks = NeighborChunks::new(|direction| {
+                    let index = FACE_DIRECTIONS.iter().position(|&d| d == direction).unwrap();
+                    neighbors[index].as_ref()
+                });
+                let mesh = chunk.build_mesh(&atlas, &neighbor_chunks);
+                JobResult::Meshed { chunk_coord, mesh, version: chunk.version() }
+            }
+            Job::Save { chunk_coord, chunk } => {
+                if let Some(dir) = &save_dir && let Err(error) = write_chunk(&chunk_path(dir, chunk_coord), &chunk) {
+                    log::warn!("Couldn't save chunk {chunk_coord:?}: {error}");
+                }
+                JobResult::Saved { chunk_coord, version: chunk.version() }
+            }
+        };
+        if results.send(result).is_err() {
+            return;
+        }
+    }
+}
+
+/// Keeps the chunks around a centre point loaded and meshed. Loading, generating, meshing and
+/// saving happen on worker threads, and `update` only takes in whatever they've finished, so it
+/// never waits on them.
+pub struct ChunkStreamer {
+    config: StreamingConfig,
+    jobs: Option<Sender<Job>>,
+    results: Receiver<JobResult>,
+    workers: Vec<JoinHandle<()>>,
+    // Chunks with a job on a worker. Each chunk has at most one at a time, and isn't evicted until
+    // it's done, so results always belong to the chunk that's loaded.
+    loading: HashSet<Vector3<i32>>,
+    meshing: HashSet<Vector3<i32>>,
+    // Modified chunks that were evicted. Loading them again takes them from here rather than the
+    // save directory, so they're kept until their save has finished, or for good if there's
+    // nowhere to save them.
+    evicted: HashMap<Vector3<i32>, VoxelChunk>,
+    // Chunks with a save on a worker. Each chunk has at most one at a time, so saves finish in the
+    // order they were made, and a later save waits in `waiting_saves` until the one before is done.
+    saving: HashSet<Vector3<i32>>,
+    waiting_saves: HashMap<Vector3<i32>, VoxelChunk>,
+}
+
+impl ChunkStreamer {
+    pub fn new(config: StreamingConfig, generator: Arc<dyn TerrainGenerator>, atlas: Arc<TextureAtlas>) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel();
+        let (result_sender, result_receiver) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = (0..config.worker_count.max(1)).map(|i| {
+            let (jobs, results, generator, atlas, save_dir) = (job_receiver.clone(), result_sender.clone(), generator.clone(), atlas.clone(), config.save_dir.clone());
+            thread::Builder::new()
+                .name(format!("chunk worker {i}"))
+                .spawn(move || run_worker(jobs, results, generator, atlas, save_dir))
+                .unwrap()
+        }).collect();
+        ChunkStreamer {
+            config,
+            jobs: Some(job_sender),
+            results: result_receiver,
+            workers,
+            loading: HashSet::new(),
+            meshing: HashSet::new(),
+            evicted: HashMap::new(),
+            saving: HashSet::new(),
+            waiting_saves: HashMap::new(),
+        }
+    }
+
+    fn send(&self, job: Job) {
+        self.jobs.as_ref().unwrap().send(job).unwrap();
+    }
+
+    // Only the newest waiting save is kept, since it has every edit the older ones had.
+    fn save(&mut self, chunk_coord: Vector3<i32>, chunk: VoxelChunk) {
+        if self.saving.insert(chunk_coord) {
+            self.send(Job::Save { chunk_coord, chunk });
+        } else {
+            self.waiting_saves.insert(chunk_coord, chunk);
+        }
+    }
+
+    fn finish_save(&mut self, chunk_coord: Vector3<i32>, version: u64) {
+        self.saving.remove(&chunk_coord);
+        if let Some(chunk) = self.waiting_saves.remove(&chunk_coord) {
+            self.save(chunk_coord, chunk);
+        }
+        // The chunk may have been loaded, edited and evicted again since, with a newer save still
+        // to come.
+        if !self.saving.contains(&chunk_coord) && self.evicted.get(&chunk_coord).is_some_and(|chunk| chunk.version() == version) {
+            self.evicted.remove(&chunk_coord);
+        }
+    }
+
+    fn n_jobs_in_flight(&self) -> usize {
+        self.loading.len() + self.meshing.len()
+    }
+
+    /// Whether no chunk is loading or meshing.
+    #[cfg(test)]
+    pub fn is_idle(&self) -> bool {
+        self.n_jobs_in_flight() == 0
+    }
+
+    // Chunks are kept a chunk further out than they're loaded, so walking back and forth over a
+    // border doesn't load and evict the same chunks over and over.
+    fn is_in_radius(&self, chunk_coord: Vector3<i32>, center_chunk: Vector3<i32>, margin: i32) -> bool {
+        let offset = chunk_coord - center_chunk;
+        let radius = self.config.load_radius + margin;
+        offset.x * offset.x + offset.z * offset.z <= radius * radius && offset.y.abs() <= self.config.vertical_radius + margin
+    }
+
+    /// Takes in finished work, evicts chunks that are too far from `center`, and starts loading
+    /// and meshing chunks that need it, nearest first. Returns the chunks that were added to the
+    /// world, at most `max_chunks_added_per_update` of them.
+    pub fn update(&mut self, world: &mut VoxelWorld, center: Vector3<i32>) -> Vec<LoadedChunk> {
+        let mut loaded = vec![];
+        while loaded.len() < self.config.max_chunks_added_per_update.max(1) && let Ok(result) = self.results.try_recv() {
+            match result {
+                JobResult::Loaded { chunk_coord, chunk, flowers } => {
+                    self.loading.remove(&chunk_coord);
+                    // An edit may have made the chunk in the meantime.
+                    if world.get_chunk(chunk_coord).is_none() {
+                        world.insert_chunk(chunk_coord, chunk);
+                        loaded.push(LoadedChunk { chunk_coord, flowers });
+                    }
+                }
+                JobResult::Meshed { chunk_coord, mesh, version } => {
+                    self.meshing.remove(&chunk_coord);
+                    if let Some(chunk) = world.get_chunk_mut(chunk_coord) {
+                        chunk.set_mesh(mesh, version);
+                    }
+                }
+                JobResult::Saved { chunk_coord, version } => self.finish_save(chunk_coord, version),
+            }
+        }
+
+        let center_chunk = voxel_to_chunk(center);
+        self.evict(world, center_chunk);
+        self.request_loads(world, center_chunk);
+        self.request_meshes(world, center_chunk);
+        loaded
+    }
+
+    fn evict(&mut self, world: &mut VoxelWorld, center_chunk: Vector3<i32>) {
+        let far: Vec<_> = world.chunks()
+            .map(|(chunk_coord, _)| chunk_coord)
+            .filter(|&chunk_coord| !self.is_in_radius(chunk_coord, center_chunk, 1) && !self.meshing.contains(&chunk_coord))
+            .collect();
+        for chunk_coord in far {
+            let chunk = world.remove_chunk(chunk_coord).unwrap();
+            if chunk.is_modified() {
+                if self.config.save_dir.is_some() {
+                    self.save(chunk_coord, chunk.snapshot());
+                }
+                self.evicted.insert(chunk_coord, chunk.snapshot());
+            }
+        }
+    }
+
+    fn request_loads(&mut self, world: &VoxelWorld, center_chunk: Vector3<i32>) {
+        let (radius, vertical_radius) = (self.config.load_radius, self.config.vertical_radius);
+        let mut missing = vec![];
+        for y in -vertical_radius..=vertical_radius {
+            for z in -radius..=radius {
+                for x in -radius..=radius {
+                    let chunk_coord = center_chunk + vec3(x, y, z);
+                    if self.is_in_radius(chunk_coord, center_chunk, 0) && world.get_chunk(chunk_coord).is_none() && !self.loading.contains(&chunk_coord) {
+                        missing.push(chunk_coord);
+                    }
+                }
+            }
+        }
+        missing.sort_by_key(|&chunk_coord| distance_squared(chunk_coord, center_chunk));
+        for chunk_coord in missing {
+            if self.n_jobs_in_flight() >= self.config.max_jobs_in_flight {
+                break;
+            }
+            // A chunk that's still being saved is read back once the save is done, unless it's in
+            // memory.
+            let evicted = self.evicted.remove(&chunk_coord);
+            if evicted.is_none() && self.saving.contains(&chunk_coord) {
+                continue;
+            }
+            self.send(Job::Load { chunk_coord, evicted });
+            self.loading.insert(chunk_coord);
+        }
+    }
+
+    fn request_meshes(&mut self, world: &VoxelWorld, center_chunk: Vector3<i32>) {
+        let mut dirty: Vec<_> = world.chunks()
+            .filter(|&(chunk_coord, chunk)| chunk.is_mesh_dirty() && !self.meshing.contains(&chunk_coord))
+            // Wait for the neighbours that are going to be loaded, or the faces against them would
+            // be meshed and then culled straight away.
+            .filter(|&(chunk_coord, _)| FACE_DIRECTIONS.iter().all(|&direction| {
+                let neighbor = chunk_coord + direction;
+                world.get_chunk(neighbor).is_some() || !self.is_in_radius(neighbor, center_chunk, 0)
+            }))
+            .map(|(chunk_coord, _)| chunk_coord)
+            .collect();
+        dirty.sort_by_key(|&chunk_coord| distance_squared(chunk_coord, center_chunk));
+        for chunk_coord in dirty {
+            if self.n_jobs_in_flight() >= self.config.max_jobs_in_flight {
+                break;
+            }
+            let chunk = world.get_chunk(chunk_coord).unwrap().snapshot();
+            let neighbors = Box::new(FACE_DIRECTIONS.map(|direction| world.get_chunk(chunk_coord + direction).map(VoxelChunk::snapshot)));
+            self.send(Job::Mesh { chunk_coord, chunk, neighbors });
+            self.meshing.insert(chunk_coord);
+        }
+    }
+
+    /// Queues a save of every modified chunk, so nothing is lost on exit. The saves finish before
+    /// the streamer is dropped.
+    pub fn save_all(&mut self, world: &mut VoxelWorld) {
+        if self.config.save_dir.is_none() {
+            return;
+        }
+        for (chunk_coord, chunk) in world.chunks_mut() {
+            if chunk.is_modified() {
+                self.save(chunk_coord, chunk.snapshot());
+                chunk.clear_modified();
+            }
+        }
+    }
+}
+
+impl Drop for ChunkStreamer {
+    fn drop(&mut self) {
+        // Saves that are waiting on an earlier one only get queued once it's done.
+        while !self.saving.is_empty() {
+            match self.results.recv() {
+                Ok(JobResult::Saved { chunk_coord, version }) => self.finish_save(chunk_coord, version),
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        // Closing the queue stops each worker once the jobs already queued are done.
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn distance_squared(a: Vector3<i32>, b: Vector3<i32>) -> i32 {
+    let offset = a - b;
+    offset.x * offset.x + offset.y * offset.y + offset.z * offset.z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, Instant};
+
+    use crate::material::{DIRT, STONE};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("henka_{}_{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    // A stone in the corner of every chunk, so every chunk has something to mesh.
+    struct CornerStones;
+
+    impl TerrainGenerator for CornerStones {
+        fn generate(&self, _: Vector3<i32>) -> GeneratedChunk {
+            let mut chunk = VoxelChunk::new();
+            chunk.set_voxel(vec3(0, 0, 0), STONE);
+            GeneratedChunk { chunk, flowers: vec![] }
+        }
+
+        fn spawn_height(&self, _: i32, _: i32) -> i32 {
+            1
+        }
+    }
+
+    fn streamer(load_radius: i32, save_dir: Option<PathBuf>) -> ChunkStreamer {
+        let config = StreamingConfig { load_radius, vertical_radius: 0, worker_count: 2, max_jobs_in_flight: 4, max_chunks_added_per_update: 2, save_dir };
+        ChunkStreamer::new(config, Arc::new(CornerStones), Arc::new(TextureAtlas::new()))
+    }
+
+    // Updates until there's nothing left to load or mesh.
+    fn settle(streamer: &mut ChunkStreamer, world: &mut VoxelWorld, center: Vector3<i32>) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            streamer.update(world, center);
+            if streamer.is_idle() {
+                return;
+            }
+            assert!(Instant::now() < deadline, "streaming didn't settle");
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn loaded_chunks(world: &VoxelWorld) -> HashSet<Vector3<i32>> {
+        world.chunks().map(|(chunk_coord, _)| chunk_coord).collect()
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let dir = temp_dir("save_round_trip");
+        let path = chunk_path(&dir, vec3(-1, 2, 3));
+        assert!(read_chunk(&path).unwrap().is_none());
+
+        let mut chunk = VoxelChunk::new();
+        chunk.set_voxel(vec3(1, 2, 3), DIRT);
+        chunk.set_voxel(vec3(31, 31, 31), STONE);
+        write_chunk(&path, &chunk).unwrap();
+        let loaded = read_chunk(&path).unwrap().unwrap();
+        assert!(chunk_coords().all(|coord| loaded.get_voxel(coord) == chunk.get_voxel(coord)));
+        assert!(!loaded.is_modified());
+
+        fs::write(&path, b"HNKC").unwrap();
+        assert!(matches!(read_chunk(&path), Err(error) if error.kind() == ErrorKind::InvalidData));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_voxels() {
+        let dir = temp_dir("rejects_unknown_voxels");
+        let path = chunk_path(&dir, vec3(0, 0, 0));
+        write_chunk(&path, &VoxelChunk::new()).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[8..12].copy_from_slice(&(MATERIALS.len() as u32).to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(matches!(read_chunk(&path), Err(error) if error.kind() == ErrorKind::InvalidData));
+
+        // The chunk is generated again instead.
+        let mut streamer = streamer(0, Some(dir.clone()));
+        let mut world = VoxelWorld::new();
+        settle(&mut streamer, &mut world, vec3(0, 0, 0));
+        assert_eq!(Some(STONE), world.get_voxel(vec3(0, 0, 0)));
+        drop(streamer);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn streams_around_center() {
+        let mut streamer = streamer(1, None);
+        let mut world = VoxelWorld::new();
+        settle(&mut streamer, &mut world, vec3(5, 5, 5));
+        let expected: HashSet<_> = [vec3(0, 0, 0), vec3(1, 0, 0), vec3(-1, 0, 0), vec3(0, 0, 1), vec3(0, 0, -1)].into();
+        assert_eq!(expected, loaded_chunks(&world));
+        for (_, chunk) in world.chunks() {
+            assert!(!chunk.is_mesh_dirty());
+            assert!(!chunk.get_mesh().0.vertices.is_empty());
+        }
+
+        // Moving one chunk over keeps the old chunks within the margin, moving further evicts them.
+        settle(&mut streamer, &mut world, vec3(37, 5, 5));
+        assert!(world.get_chunk(vec3(-1, 0, 0)).is_some());
+        assert!(world.get_chunk(vec3(2, 0, 0)).is_some());
+        settle(&mut streamer, &mut world, vec3(100, 5, 5));
+        assert!(loaded_chunks(&world).iter().all(|chunk_coord| chunk_coord.x >= 1));
+    }
+
+    #[test]
+    fn adds_a_few_chunks_per_update() {
+        let mut streamer = streamer(2, None);
+        let mut world = VoxelWorld::new();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            assert!(streamer.update(&mut world, vec3(0, 0, 0)).len() <= 2);
+            if streamer.is_idle() {
+                break;
+            }
+            assert!(Instant::now() < deadline, "streaming didn't settle");
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(13, world.chunks().count());
+    }
+
+    #[test]
+    fn keeps_edits_in_memory() {
+        let mut streamer = streamer(0, None);
+        let mut world = VoxelWorld::new();
+        settle(&mut streamer, &mut world, vec3(0, 0, 0));
+        world.set_voxel(vec3(5, 5, 5), DIRT);
+        settle(&mut streamer, &mut world, vec3(1000, 0, 0));
+        assert!(!world.is_loaded(vec3(5, 5, 5)));
+        settle(&mut streamer, &mut world, vec3(0, 0, 0));
+        assert_eq!(Some(DIRT), world.get_voxel(vec3(5, 5, 5)));
+    }
+
+    // Updates until every save is done.
+    fn finish_saves(streamer: &mut ChunkStreamer, world: &mut VoxelWorld, center: Vector3<i32>) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !streamer.saving.is_empty() {
+            assert!(Instant::now() < deadline, "saves didn't finish");
+            thread::sleep(Duration::from_millis(1));
+            streamer.update(world, center);
+        }
+    }
+
+    #[test]
+    fn one_save_at_a_time() {
+        let dir = temp_dir("one_save_at_a_time");
+        let mut streamer = streamer(0, Some(dir.clone()));
+        let mut world = VoxelWorld::new();
+        let chunk_coord = vec3(3, 0, 0);
+        let mut chunk = VoxelChunk::new();
+        chunk.set_voxel(vec3(1, 1, 1), DIRT);
+        streamer.save(chunk_coord, chunk.snapshot());
+        chunk.set_voxel(vec3(2, 2, 2), DIRT);
+        streamer.save(chunk_coord, chunk.snapshot());
+        chunk.set_voxel(vec3(3, 3, 3), DIRT);
+        streamer.save(chunk_coord, chunk.snapshot());
+
+        // Nothing's taken in until `update`, so the first save can't have finished as far as the
+        // streamer knows, and only the newest of the others waits for it.
+        assert!(streamer.saving.contains(&chunk_coord));
+        assert_eq!(Some(chunk.version()), streamer.waiting_saves.get(&chunk_coord).map(VoxelChunk::version));
+        finish_saves(&mut streamer, &mut world, vec3(1000, 0, 0));
+        assert!(streamer.waiting_saves.is_empty());
+        let saved = read_chunk(&chunk_path(&dir, chunk_coord)).unwrap().unwrap();
+        assert_eq!(DIRT, saved.get_voxel(vec3(3, 3, 3)));
+        drop(streamer);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn evicts_the_same_chunk_twice() {
+        let dir = temp_dir("evicts_the_same_chunk_twice");
+        let mut streamer = streamer(0, Some(dir.clone()));
+        let mut world = VoxelWorld::new();
+        settle(&mut streamer, &mut world, vec3(0, 0, 0));
+        world.set_voxel(vec3(5, 5, 5), DIRT);
+        streamer.update(&mut world, vec3(1000, 0, 0));
+        assert!(!world.is_loaded(vec3(5, 5, 5)));
+
+        // It comes back from memory whether or not its save has finished.
+        settle(&mut streamer, &mut world, vec3(0, 0, 0));
+        world.set_voxel(vec3(6, 6, 6), DIRT);
+        streamer.update(&mut world, vec3(1000, 0, 0));
+        finish_saves(&mut streamer, &mut world, vec3(1000, 0, 0));
+        assert!(streamer.evicted.is_empty());
+
+        settle(&mut streamer, &mut world, vec3(0, 0, 0));
+        assert_eq!(Some(DIRT), world.get_voxel(vec3(5, 5, 5)));
+        assert_eq!(Some(DIRT), world.get_voxel(vec3(6, 6, 6)));
+        assert!(fs::read_dir(&dir).unwrap().all(|entry| entry.unwrap().path().extension().unwrap() == "chunk"));
+        drop(streamer);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn drops_evicted_chunks_once_saved() {
+        let dir = temp_dir("drops_evicted_chunks_once_saved");
+        let mut streamer = streamer(0, Some(dir.clone()));
+        let mut world = VoxelWorld::new();
+        settle(&mut streamer, &mut world, vec3(0, 0, 0));
+        world.set_voxel(vec3(5, 5, 5), DIRT);
+        settle(&mut streamer, &mut world, vec3(1000, 0, 0));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !streamer.evicted.is_empty() {
+            assert!(Instant::now() < deadline, "the evicted chunk was never saved");
+            thread::sleep(Duration::from_millis(1));
+            streamer.update(&mut world, vec3(1000, 0, 0));
+        }
+        settle(&mut streamer, &mut world, vec3(0, 0, 0));
+        assert_eq!(Some(DIRT), world.get_voxel(vec3(5, 5, 5)));
+        drop(streamer);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn saves_edits_to_disk() {
+        let dir = temp_dir("saves_edits_to_disk");
+        {
+            let mut streamer = streamer(0, Some(dir.clone()));
+            let mut world = VoxelWorld::new();
+            settle(&mut streamer, &mut world, vec3(0, 0, 0));
+            world.set_voxel(vec3(5, 5, 5), DIRT);
+            streamer.save_all(&mut world);
+        }
+        let mut streamer = streamer(0, Some(dir.clone()));
+        let mut world = VoxelWorld::new();
+        settle(&mut streamer, &mut world, vec3(0, 0, 0));
+        assert_eq!(Some(DIRT), world.get_voxel(vec3(5, 5, 5)));
+        assert_eq!(Some(STONE), world.get_voxel(vec3(0, 0, 0)));
+        drop(streamer);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 
-use cgmath::{Point3, point3, Vector3, vec3};
+use cgmath::{EuclideanSpace, Point3, point3, Vector3, vec3};
 use rand::Rng;
 
 use crate::fixed_point::Fixed;
-use crate::voxel::VoxelChunk;
+use crate::material::{AIR, material};
+use crate::voxel_world::VoxelWorld;
 
 const FLOWER_MATURITY_AGE: u32 = 20;
 const FLOWER_LIFESPAN: u32 = 120;
@@ -18,13 +19,13 @@ pub struct EcosimEntity {
 }
 
 impl EcosimEntity {
-    pub fn new(voxel_coord: Vector3<usize>) -> Self {
+    pub fn new(voxel_coord: Vector3<i32>) -> Self {
         let mut rng = rand::rng();
         EcosimEntity {
             position: point3(
-                Fixed::new(voxel_coord.x as i32, rng.random_range(16..=240)),
-                Fixed::new(voxel_coord.y as i32, 0),
-                Fixed::new(voxel_coord.z as i32, rng.random_range(16..=240)),
+                Fixed::new(voxel_coord.x, rng.random_range(16..=240)),
+                Fixed::new(voxel_coord.y, 0),
+                Fixed::new(voxel_coord.z, rng.random_range(16..=240)),
             ),
             genome: 0,
             age_ticks: 0,
@@ -33,17 +34,18 @@ impl EcosimEntity {
         }
     }
 
-    pub fn voxel_coord(&self) -> Vector3<i32> {
-        vec3(
-            self.position.x.to_f32().floor() as i32,
-            self.position.y.to_f32().floor() as i32,
-            self.position.z.to_f32().floor() as i32,
-        )
+    pub fn with_genome(position: Point3<Fixed>, genome: u32) -> Self {
+        EcosimEntity {
+            position,
+            genome,
+            age_ticks: 0,
+            stress: 0,
+            dead_ticks: None,
+        }
     }
 
-    pub fn randomize_genome(&mut self) {
-        let mut rng = rand::rng();
-        self.genome = rng.random();
+    pub fn voxel_coord(&self) -> Vector3<i32> {
+        self.position.to_vec().map(Fixed::floor_i32)
     }
 
     fn mutate_genome(&mut self) {
@@ -99,18 +101,20 @@ const ADJACENCIES: &[(i32, i32, i32)] = &[
     (0, -1, -1),
 ];
 
-fn can_entity_grow_into_coord(coord: Vector3<i32>, voxels: &VoxelChunk) -> bool {
-    if voxels.is_i32_out_of_bounds(coord) {
-        return false;
-    }
-    let below_coord = vec3(coord.x, coord.y - 1, coord.z);
-    if voxels.is_i32_out_of_bounds(below_coord) {
-        return false;
+fn can_entity_grow_into_coord(coord: Vector3<i32>, voxels: &VoxelWorld) -> bool {
+    // Nothing grows into or onto chunks that aren't loaded.
+    let below = voxels.get_neighbor(coord, vec3(0, -1, 0));
+    voxels.get_voxel(coord) == Some(AIR) && below.is_some_and(|voxel| material(voxel).supports_flowers)
+}
+
+/// Kills the living entities in the voxel at `coord`, e.g. when the ground under them is removed.
+pub fn kill_entities_at(entities: &mut [EcosimEntity], coord: Vector3<i32>) {
+    for entity in entities.iter_mut().filter(|e| e.dead_ticks.is_none() && e.voxel_coord() == coord) {
+        entity.dead_ticks = Some(0);
     }
-    voxels.get_voxel_i32(coord) == 0 && voxels.get_voxel_i32(below_coord) == 1
 }
 
-pub fn ecosim_tick(entities: &mut Vec<EcosimEntity>, voxels: &VoxelChunk) {
+pub fn ecosim_tick(entities: &mut Vec<EcosimEntity>, voxels: &VoxelWorld) {
     let mut rng = rand::rng();
     let mut new_entities = vec![];
     let mut coord_population: HashMap<Vector3<i32>, u32> = HashMap::new();
@@ -135,7 +139,7 @@ pub fn ecosim_tick(entities: &mut Vec<EcosimEntity>, voxels: &VoxelChunk) {
         for &(dx, dy, dz) in ADJACENCIES.iter() {
             let adj = coord_i32 + vec3(dx, dy, dz);
             if entity.dead_ticks.is_none() && entity.age_ticks >= FLOWER_MATURITY_AGE && *coord_population.get(&adj).unwrap_or(&0u32) < 6 && can_entity_grow_into_coord(adj, voxels) && rng.random::<f32>() < 0.006 {
-                let mut new_entity = EcosimEntity::new(adj.map(|i| i as usize));
+                let mut new_entity = EcosimEntity::new(adj);
                 new_entity.genome = entity.genome;
                 new_entity.mutate_genome();
                 new_entities.push(new_entity);
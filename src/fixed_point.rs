@@ -1,179 +1,712 @@
+use std::error::Error;
 use std::fmt;
 use std::ops;
+use std::str::FromStr;
 
 use cgmath::{point3, Point3, vec3, Vector3};
+use num_traits::{Bounded, Num, NumCast, One, Signed, ToPrimitive, Zero};
 
-const FRACTION_BITS: u32 = 8;
-const WHOLE_BITS: u32 = 23;
+// The trig functions work internally on i64 values with 30 fraction bits, so that rounding errors
+// stay well below one epsilon. Everything is integer arithmetic, so results are bit-exact on every
+// platform.
+const TRIG_BITS: u32 = 30;
+const TRIG_PI: i64 = 3373259426;
+const TRIG_FRAC_PI_2: i64 = 1686629713;
+const TRIG_TAU: i64 = 6746518852;
 
-pub const DENOMINATOR: u32 = 2u32.pow(FRACTION_BITS);
+// atan(2^-i) for each CORDIC iteration i.
+const TRIG_ATAN_TABLE: [i64; 31] = [
+    843314857, 497837829, 263043837, 133525159, 67021687, 33543516, 16775851, 8388437, 4194283,
+    2097149, 1048576, 524288, 262144, 131072, 65536, 32768, 16384, 8192, 4096, 2048, 1024, 512,
+    256, 128, 64, 32, 16, 8, 4, 2, 1,
+];
 
-fn add_unsigned(lw: u32, lf: u32, rw: u32, rf: u32) -> (u32, u32) {
-    let mut new_fraction = lf + rf;
-    let mut overflow = 0;
-    if new_fraction >= DENOMINATOR {
-        new_fraction -= DENOMINATOR;
-        overflow += 1;
+// Sine of an angle in radians with `TRIG_BITS` fraction bits, using a Taylor series after reducing
+// the angle to [-pi/2, pi/2].
+fn sin_trig(angle: i64) -> i64 {
+    let mut x = angle % TRIG_TAU;
+    if x > TRIG_PI {
+        x -= TRIG_TAU;
+    } else if x < -TRIG_PI {
+        x += TRIG_TAU;
     }
-    (lw + rw + overflow, new_fraction)
+    if x > TRIG_FRAC_PI_2 {
+        x = TRIG_PI - x;
+    } else if x < -TRIG_FRAC_PI_2 {
+        x = -TRIG_PI - x;
+    }
+    let one = 1i64 << TRIG_BITS;
+    let x2 = (x * x) >> TRIG_BITS;
+    let mut series = one;
+    for divisor in [110, 72, 42, 20, 6] {
+        series = one - ((x2 * series) >> TRIG_BITS) / divisor;
+    }
+    (x * series) >> TRIG_BITS
 }
 
-fn subtract_unsigned(lw: u32, lf: u32, rw: u32, rf: u32) -> (bool, u32, u32) {
-    let new_fraction;
-    let mut borrow = 0;
-    if lf >= rf {
-        new_fraction = lf - rf;
-    } else {
-        new_fraction = lf + DENOMINATOR - rf;
-        borrow = 1;
-    }
-    let rw_with_borrow = rw + borrow;
-    if rw_with_borrow > lw {
-        let whole_diff = rw_with_borrow - lw;
-        if new_fraction == 0 {
-            (true, whole_diff, 0)
-        } else {
-            (true, whole_diff - 1, DENOMINATOR - new_fraction)
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ParseFixedError {
+    Empty,
+    InvalidDigit,
+    Overflow,
+}
+
+impl fmt::Display for ParseFixedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseFixedError::Empty => write!(f, "cannot parse Fixed from empty string"),
+            ParseFixedError::InvalidDigit => write!(f, "invalid digit found in string"),
+            ParseFixedError::Overflow => write!(f, "number too large to fit in Fixed"),
         }
-    } else {
-        (false, lw - rw_with_borrow, new_fraction)
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
-pub struct Fixed(u32);
+impl Error for ParseFixedError {}
 
-impl Fixed {
-    pub const ZERO: Fixed = Fixed(0);
-    pub const EPSILON: Fixed = Fixed(1);
+/// A fixed-point number with `FRAC` fraction bits and `31 - FRAC` whole bits, stored as a count of
+/// epsilons in an `i32`. The range is symmetric, so `MIN` is `-MAX` and negating never overflows.
+/// All precisions share one implementation; use `convert` to move between them.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct FixedPoint<const FRAC: u32>(i32);
 
-    pub const ZERO_POINT: Point3<Fixed> = point3(Self::ZERO, Self::ZERO, Self::ZERO);
-    pub const ZERO_VECTOR: Vector3<Fixed> = vec3(Self::ZERO, Self::ZERO, Self::ZERO);
+/// Q23.8, with a precision of 1/256. This is what physics uses.
+pub type Fixed = FixedPoint<8>;
 
-    pub fn new(whole: i32, fraction: u32) -> Self {
+/// Q15.16, with a precision of 1/65536, for slow drift and fine velocities.
+#[allow(unused)]
+pub type FixedFine = FixedPoint<16>;
+
+impl<const FRAC: u32> FixedPoint<FRAC> {
+    pub const FRACTION_BITS: u32 = FRAC;
+    pub const WHOLE_BITS: u32 = 31 - FRAC;
+    pub const DENOMINATOR: u32 = 1 << FRAC;
+
+    pub const ZERO: Self = Self(0);
+    pub const ONE: Self = Self(1 << FRAC);
+    pub const EPSILON: Self = Self(1);
+    pub const MAX: Self = Self(i32::MAX);
+    pub const MIN: Self = Self(-i32::MAX);
+
+    pub const ZERO_POINT: Point3<Self> = point3(Self::ZERO, Self::ZERO, Self::ZERO);
+    pub const ZERO_VECTOR: Vector3<Self> = vec3(Self::ZERO, Self::ZERO, Self::ZERO);
+
+    pub const fn new(whole: i32, fraction: u32) -> Self {
         Self::from_parts(whole < 0, whole.abs() as u32, fraction)
     }
 
-    pub fn from_parts(negative: bool, whole: u32, fraction: u32) -> Self {
-        assert!(whole < 2u32.pow(WHOLE_BITS));
-        assert!(fraction < DENOMINATOR);
-        let sign_bit = if negative { 1 } else { 0 };
-        Fixed((sign_bit << 31) | ((whole as u32) << FRACTION_BITS) | fraction)
+    pub const fn from_parts(negative: bool, whole: u32, fraction: u32) -> Self {
+        // The trig functions need some spare bits below `TRIG_BITS`.
+        const { assert!(FRAC >= 1 && FRAC <= 24, "FixedPoint supports 1 to 24 fraction bits") };
+        assert!(whole < 2u32.pow(Self::WHOLE_BITS));
+        assert!(fraction < Self::DENOMINATOR);
+        let magnitude = ((whole << FRAC) | fraction) as i32;
+        Self(if negative { -magnitude } else { magnitude })
     }
 
+    /// Converts to another precision. Extra fraction bits are truncated toward zero. Panics if the
+    /// value doesn't fit in the new precision.
+    pub fn convert<const TO: u32>(self) -> FixedPoint<TO> {
+        match self.checked_convert() {
+            Some(f) => f,
+            None => panic!("{:?} doesn't fit in {} fraction bits", self, TO),
+        }
+    }
+
+    pub fn checked_convert<const TO: u32>(self) -> Option<FixedPoint<TO>> {
+        let value = self.signed_epsilons();
+        let converted = if TO >= FRAC { value << (TO - FRAC) } else { value / (1 << (FRAC - TO)) };
+        FixedPoint::<TO>::checked_from_signed_epsilons(converted)
+    }
+
+    /// Rounds to the nearest epsilon. Panics if the value doesn't fit.
     pub fn from_f32(value: f32) -> Self {
-        let whole = value.trunc().abs() as u32;
-        let fraction = ((value - value.trunc()).abs() * DENOMINATOR as f32).round() as u32;
-        Self::from_parts(value < 0.0, whole, fraction)
+        match Self::checked_from_f32(value) {
+            Some(f) => f,
+            None => panic!("{} doesn't fit in Fixed", value),
+        }
+    }
+
+    /// `None` if the value doesn't fit, or is NaN.
+    pub fn checked_from_f32(value: f32) -> Option<Self> {
+        // Rounding the whole value at once, rather than just the fraction, carries a fraction that
+        // rounds up to one into the whole part.
+        let epsilons = (value as f64 * Self::DENOMINATOR as f64).round();
+        if epsilons.is_nan() || epsilons.abs() > i32::MAX as f64 {
+            return None;
+        }
+        Some(Self::from_signed_epsilons(epsilons as i64))
     }
 
-    pub fn vector3_from_f32(v: Vector3<f32>) -> Vector3<Fixed> {
+    pub fn vector3_from_f32(v: Vector3<f32>) -> Vector3<Self> {
         Vector3::new(v.x.into(), v.y.into(), v.z.into())
     }
 
-    pub fn vector3_to_f32(v: Vector3<Fixed>) -> Vector3<f32> {
+    pub fn vector3_to_f32(v: Vector3<Self>) -> Vector3<f32> {
         Vector3::new(v.x.into(), v.y.into(), v.z.into())
     }
 
-    pub fn point3_from_f32(v: Point3<f32>) -> Point3<Fixed> {
+    pub fn point3_from_f32(v: Point3<f32>) -> Point3<Self> {
         Point3::new(v.x.into(), v.y.into(), v.z.into())
     }
 
-    pub fn point3_to_f32(v: Point3<Fixed>) -> Point3<f32> {
+    pub fn point3_to_f32(v: Point3<Self>) -> Point3<f32> {
         Point3::new(v.x.into(), v.y.into(), v.z.into())
     }
 
     pub fn unpack(&self) -> (bool, u32, u32) {
-        let whole = (0x7fffff00 & self.0) >> FRACTION_BITS;
-        let fraction = 0xff & self.0;
-        (self.is_negative(), whole, fraction)
+        let magnitude = self.epsilons();
+        (self.is_negative(), magnitude >> FRAC, magnitude & (Self::DENOMINATOR - 1))
     }
 
     pub fn is_negative(&self) -> bool {
-        (self.0 & 0x80000000) > 0
+        self.0 < 0
     }
 
     pub fn to_f32(&self) -> f32 {
         let (negative, whole, fraction) = self.unpack();
-        (whole as f32 + (fraction as f32 / DENOMINATOR as f32)) * if negative { -1.0 } else { 1.0 }
+        (whole as f32 + (fraction as f32 / Self::DENOMINATOR as f32)) * if negative { -1.0 } else { 1.0 }
     }
 
     pub fn epsilons(&self) -> u32 {
-        let (_negative, whole, fraction) = self.unpack();
-        whole * DENOMINATOR + fraction
+        self.0.unsigned_abs()
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    /// Returns -1, 0 or 1.
+    pub fn signum(self) -> Self {
+        match self.signed_epsilons().signum() {
+            0 => Self::ZERO,
+            1 => Self::ONE,
+            _ => -Self::ONE,
+        }
     }
+
+    pub fn floor(self) -> Self {
+        Self::from_whole(self.floor_i32())
+    }
+
+    pub fn ceil(self) -> Self {
+        Self::from_whole(self.ceil_i32())
+    }
+
+    /// Rounds half away from zero, like `f32::round`.
+    pub fn round(self) -> Self {
+        Self::from_whole(self.round_i32())
+    }
+
+    pub fn trunc(self) -> Self {
+        Self::from_whole(self.trunc_i32())
+    }
+
+    pub fn floor_i32(self) -> i32 {
+        self.signed_epsilons().div_euclid(Self::DENOMINATOR as i64) as i32
+    }
+
+    pub fn ceil_i32(self) -> i32 {
+        -(-self).floor_i32()
+    }
+
+    pub fn round_i32(self) -> i32 {
+        let (negative, whole, fraction) = self.unpack();
+        let rounded = (whole + if fraction >= Self::DENOMINATOR / 2 { 1 } else { 0 }) as i32;
+        if negative { -rounded } else { rounded }
+    }
+
+    pub fn trunc_i32(self) -> i32 {
+        let (negative, whole, _fraction) = self.unpack();
+        if negative { -(whole as i32) } else { whole as i32 }
+    }
+
+    // Panics if the value doesn't fit, e.g. rounding up the largest values.
+    fn from_whole(whole: i32) -> Self {
+        Self::from_signed_epsilons(whole as i64 * Self::DENOMINATOR as i64)
+    }
+
+    // The operators panic on overflow, the same as `from_parts`. These variants are for callers
+    // that would rather handle it. Wrapping treats the range as circular, so MAX + EPSILON is MIN.
+    // Adding and subtracting stay in i32 since physics does them for every epsilon step; `i32::MIN`
+    // is outside the symmetric range, so it counts as an overflow too.
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).filter(|&sum| sum != i32::MIN).map(Self)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).filter(|&difference| difference != i32::MIN).map(Self)
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Option<Self> {
+        Self::checked_from_signed_epsilons(self.signed_epsilons() * rhs.signed_epsilons() / Self::DENOMINATOR as i64)
+    }
+
+    /// Also returns `None` when dividing by zero.
+    pub fn checked_div(self, rhs: Self) -> Option<Self> {
+        if rhs.is_zero() {
+            return None;
+        }
+        Self::checked_from_signed_epsilons(self.signed_epsilons() * Self::DENOMINATOR as i64 / rhs.signed_epsilons())
+    }
+
+    pub fn saturating_add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0).max(-i32::MAX))
+    }
+
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0).max(-i32::MAX))
+    }
+
+    pub fn saturating_mul(self, rhs: Self) -> Self {
+        Self::saturating_from_signed_epsilons(self.signed_epsilons() * rhs.signed_epsilons() / Self::DENOMINATOR as i64)
+    }
+
+    pub fn wrapping_add(self, rhs: Self) -> Self {
+        Self::wrapping_from_signed_epsilons(self.signed_epsilons() + rhs.signed_epsilons())
+    }
+
+    pub fn wrapping_sub(self, rhs: Self) -> Self {
+        Self::wrapping_from_signed_epsilons(self.signed_epsilons() - rhs.signed_epsilons())
+    }
+
+    pub fn wrapping_mul(self, rhs: Self) -> Self {
+        Self::wrapping_from_signed_epsilons(self.signed_epsilons() * rhs.signed_epsilons() / Self::DENOMINATOR as i64)
+    }
+
+    pub fn pi() -> Self {
+        Self::from_trig(TRIG_PI)
+    }
+
+    /// Rounds toward zero. Panics if `self` is negative.
+    pub fn sqrt(self) -> Self {
+        assert!(!self.is_negative(), "square root of negative Fixed {:?}", self);
+        let squared_epsilons = (self.epsilons() as u64) << FRAC;
+        Self::from_signed_epsilons(squared_epsilons.isqrt() as i64)
+    }
+
+    /// Takes an angle in radians.
+    pub fn sin(self) -> Self {
+        Self::from_trig(sin_trig(self.to_trig()))
+    }
+
+    /// Takes an angle in radians.
+    pub fn cos(self) -> Self {
+        Self::from_trig(sin_trig(self.to_trig() + TRIG_FRAC_PI_2))
+    }
+
+    /// Angle in radians of the point (`x`, `self`) in the range [-pi, pi], like `f32::atan2`.
+    /// Returns zero when both are zero.
+    pub fn atan2(self, x: Self) -> Self {
+        let (mut x, mut y) = (x.signed_epsilons(), self.signed_epsilons());
+        if x == 0 && y == 0 {
+            return Self::ZERO;
+        }
+        // CORDIC only converges in the right half-plane, so rotate the left half-plane by pi first.
+        let mut angle = 0;
+        if x < 0 {
+            angle = if y < 0 { -TRIG_PI } else { TRIG_PI };
+            x = -x;
+            y = -y;
+        }
+        // Extra precision for the shifts below. The CORDIC gain of ~1.65 still fits in an i64.
+        x <<= 24;
+        y <<= 24;
+        for (i, &atan) in TRIG_ATAN_TABLE.iter().enumerate() {
+            let (dx, dy) = (y >> i, x >> i);
+            if y > 0 {
+                x += dx;
+                y -= dy;
+                angle += atan;
+            } else {
+                x -= dx;
+                y += dy;
+                angle -= atan;
+            }
+        }
+        Self::from_trig(angle)
+    }
+
+    /// Rounds toward zero.
+    pub fn vector3_magnitude(v: Vector3<Self>) -> Self {
+        let squared: u128 = [v.x, v.y, v.z].iter().map(|c| (c.epsilons() as u128).pow(2)).sum();
+        Self::from_signed_epsilons(squared.isqrt() as i64)
+    }
+
+    /// Each component rounds toward zero, so the result can be slightly shorter than one. The zero
+    /// vector is returned unchanged.
+    pub fn vector3_normalize(v: Vector3<Self>) -> Vector3<Self> {
+        let magnitude = Self::vector3_magnitude(v).signed_epsilons();
+        if magnitude == 0 {
+            return v;
+        }
+        v.map(|c| Self::from_signed_epsilons(c.signed_epsilons() * Self::DENOMINATOR as i64 / magnitude))
+    }
+
+    fn signed_epsilons(&self) -> i64 {
+        self.0 as i64
+    }
+
+    fn checked_from_signed_epsilons(value: i64) -> Option<Self> {
+        if value.unsigned_abs() > i32::MAX as u64 {
+            return None;
+        }
+        Some(Self(value as i32))
+    }
+
+    fn saturating_from_signed_epsilons(value: i64) -> Self {
+        let max = Self::MAX.signed_epsilons();
+        Self::from_signed_epsilons(value.clamp(-max, max))
+    }
+
+    fn wrapping_from_signed_epsilons(value: i64) -> Self {
+        let max = Self::MAX.signed_epsilons();
+        Self::from_signed_epsilons((value + max).rem_euclid(2 * max + 1) - max)
+    }
+
+    // Panics if the value doesn't fit, the same as `from_parts`.
+    fn from_signed_epsilons(value: i64) -> Self {
+        match Self::checked_from_signed_epsilons(value) {
+            Some(f) => f,
+            None => panic!("Fixed overflow: {} epsilons", value),
+        }
+    }
+
+    fn to_trig(self) -> i64 {
+        self.signed_epsilons() << (TRIG_BITS - FRAC)
+    }
+
+    // Rounds half away from zero, so that e.g. sin(-x) == -sin(x) exactly.
+    fn from_trig(value: i64) -> Self {
+        let shift = TRIG_BITS - FRAC;
+        let magnitude = (value.unsigned_abs() + (1 << (shift - 1))) >> shift;
+        Self::from_signed_epsilons(if value < 0 { -(magnitude as i64) } else { magnitude as i64 })
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        let epsilons = (value * Self::DENOMINATOR as f64).round();
+        if !epsilons.is_finite() {
+            return None;
+        }
+        Self::checked_from_signed_epsilons(epsilons as i64)
+    }
+}
+
+// Parses the fraction digits exactly rather than via a float, rounding half away from zero. `digits`
+// must already be validated for `radix`.
+fn parse_fraction_epsilons(digits: &str, radix: u32, fraction_bits: u32) -> u64 {
+    const GUARD_BITS: u32 = 32;
+    let mut scaled: u128 = 0;
+    for c in digits.chars().rev() {
+        let digit = c.to_digit(radix).unwrap() as u128;
+        scaled = ((digit << (fraction_bits + GUARD_BITS)) + scaled) / radix as u128;
+    }
+    ((scaled + (1 << (GUARD_BITS - 1))) >> GUARD_BITS) as u64
 }
 
-impl fmt::Debug for Fixed {
+impl<const FRAC: u32> fmt::Debug for FixedPoint<FRAC> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let (negative, whole, fraction) = self.unpack();
-        write!(f, "Fixed({}{}.{:03})", if negative { "-" } else { "+" }, whole, fraction)
+        let digits = (Self::DENOMINATOR - 1).ilog10() as usize + 1;
+        write!(f, "Fixed({}{}.{:0digits$})", if negative { "-" } else { "+" }, whole, fraction)
+    }
+}
+
+// Prints the exact decimal value, which always fits in `FRAC` decimal places since each epsilon is
+// 5^FRAC / 10^FRAC. Trailing zeros are dropped unless a precision is given, in which case the value
+// rounds half away from zero like `FromStr`.
+impl<const FRAC: u32> fmt::Display for FixedPoint<FRAC> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (negative, mut whole, fraction) = self.unpack();
+        let mut decimal = fraction as u128 * 5u128.pow(FRAC);
+        let mut digits = FRAC as usize;
+        // Zeros past the exact digits, which are added as text so a large precision can't overflow.
+        let mut padding = 0;
+        if let Some(precision) = f.precision() {
+            if precision < digits {
+                let divisor = 10u128.pow((digits - precision) as u32);
+                decimal = (decimal + divisor / 2) / divisor;
+                if decimal == 10u128.pow(precision as u32) {
+                    whole += 1;
+                    decimal = 0;
+                }
+                digits = precision;
+            } else {
+                padding = precision - digits;
+            }
+        } else {
+            while digits > 0 && decimal.is_multiple_of(10) {
+                decimal /= 10;
+                digits -= 1;
+            }
+        }
+        let text = if digits + padding == 0 { whole.to_string() } else { format!("{}.{:0digits$}{}", whole, decimal, "0".repeat(padding)) };
+        f.pad_integral(!negative, "", &text)
     }
 }
 
-impl From<f32> for Fixed {
+impl<const FRAC: u32> FromStr for FixedPoint<FRAC> {
+    type Err = ParseFixedError;
+
+    /// Parses a decimal like "-2.5" exactly, without going through a float.
+    fn from_str(s: &str) -> Result<Self, ParseFixedError> {
+        Self::from_str_radix(s, 10)
+    }
+}
+
+// Values are stored as the raw signed epsilon count, so they round-trip exactly. The format depends
+// on `FRAC`, so a value saved as `Fixed` must be loaded as `Fixed`.
+#[cfg(feature = "serde")]
+impl<const FRAC: u32> serde::Serialize for FixedPoint<FRAC> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.signed_epsilons() as i32)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, const FRAC: u32> serde::Deserialize<'de> for FixedPoint<FRAC> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let epsilons = i32::deserialize(deserializer)?;
+        Self::checked_from_signed_epsilons(epsilons as i64)
+            .ok_or_else(|| serde::de::Error::custom(format!("{} epsilons is out of range for Fixed", epsilons)))
+    }
+}
+
+impl<const FRAC: u32> From<f32> for FixedPoint<FRAC> {
     fn from(value: f32) -> Self {
         Self::from_f32(value)
     }
 }
 
-impl From<Fixed> for f32 {
-    fn from(value: Fixed) -> Self {
+impl<const FRAC: u32> From<FixedPoint<FRAC>> for f32 {
+    fn from(value: FixedPoint<FRAC>) -> Self {
         value.to_f32()
     }
 }
 
-impl ops::Add<Fixed> for Fixed {
-    type Output = Fixed;
+impl<const FRAC: u32> ops::Add<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    type Output = Self;
 
-    fn add(self, rhs: Fixed) -> Fixed {
-        let (lneg, lw, lf) = self.unpack();
-        let (rneg, rw, rf) = rhs.unpack();
-        if lneg == rneg {
-            let (w, f) = add_unsigned(lw, lf, rw, rf);
-            Fixed::from_parts(lneg, w, f)
-        } else {
-            let (neg, w, f) = subtract_unsigned(lw, lf, rw, rf);
-            Fixed::from_parts(neg == rneg, w, f)
+    fn add(self, rhs: Self) -> Self {
+        match self.checked_add(rhs) {
+            Some(sum) => sum,
+            None => panic!("Fixed overflow: {:?} + {:?}", self, rhs),
         }
     }
 }
 
-impl ops::AddAssign<Fixed> for Fixed {
-    fn add_assign(&mut self, rhs: Fixed) {
+impl<const FRAC: u32> ops::AddAssign<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    fn add_assign(&mut self, rhs: Self) {
         *self = *self + rhs;
     }
 }
 
-impl ops::Sub<Fixed> for Fixed {
-    type Output = Fixed;
+impl<const FRAC: u32> ops::Sub<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    type Output = Self;
 
-    fn sub(self, rhs: Fixed) -> Fixed {
-        self + (-rhs)
+    fn sub(self, rhs: Self) -> Self {
+        match self.checked_sub(rhs) {
+            Some(difference) => difference,
+            None => panic!("Fixed overflow: {:?} - {:?}", self, rhs),
+        }
     }
 }
 
-impl ops::SubAssign<Fixed> for Fixed {
-    fn sub_assign(&mut self, rhs: Fixed) {
+impl<const FRAC: u32> ops::SubAssign<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    fn sub_assign(&mut self, rhs: Self) {
         *self = *self - rhs;
     }
 }
 
-impl ops::Neg for Fixed {
-    type Output = Fixed;
+// Multiplication, division and remainder round toward zero, like integer division. This means
+// repeatedly scaling a value by a factor less than one (e.g. friction) always reaches zero. All
+// of them panic on overflow, and division and remainder panic when dividing by zero.
+
+impl<const FRAC: u32> ops::Mul<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::from_signed_epsilons(self.signed_epsilons() * rhs.signed_epsilons() / Self::DENOMINATOR as i64)
+    }
+}
+
+impl<const FRAC: u32> ops::MulAssign<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl<const FRAC: u32> ops::Mul<i32> for FixedPoint<FRAC> {
+    type Output = Self;
+
+    fn mul(self, rhs: i32) -> Self {
+        Self::from_signed_epsilons(self.signed_epsilons() * rhs as i64)
+    }
+}
+
+impl<const FRAC: u32> ops::MulAssign<i32> for FixedPoint<FRAC> {
+    fn mul_assign(&mut self, rhs: i32) {
+        *self = *self * rhs;
+    }
+}
+
+impl<const FRAC: u32> ops::Mul<FixedPoint<FRAC>> for i32 {
+    type Output = FixedPoint<FRAC>;
+
+    fn mul(self, rhs: FixedPoint<FRAC>) -> FixedPoint<FRAC> {
+        rhs * self
+    }
+}
+
+impl<const FRAC: u32> ops::Div<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Self::from_signed_epsilons(self.signed_epsilons() * Self::DENOMINATOR as i64 / rhs.signed_epsilons())
+    }
+}
+
+impl<const FRAC: u32> ops::DivAssign<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    fn div_assign(&mut self, rhs: Self) {
+        *self = *self / rhs;
+    }
+}
+
+impl<const FRAC: u32> ops::Rem<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    type Output = Self;
+
+    fn rem(self, rhs: Self) -> Self {
+        Self::from_signed_epsilons(self.signed_epsilons() % rhs.signed_epsilons())
+    }
+}
+
+impl<const FRAC: u32> ops::RemAssign<FixedPoint<FRAC>> for FixedPoint<FRAC> {
+    fn rem_assign(&mut self, rhs: Self) {
+        *self = *self % rhs;
+    }
+}
+
+impl<const FRAC: u32> ops::Neg for FixedPoint<FRAC> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+// The traits below are what cgmath needs to treat `Fixed` as a `BaseNum`, which gives us vector and
+// point arithmetic, `dot` and `magnitude2` on `Vector3<Fixed>` and `Point3<Fixed>`.
 
-    fn neg(self) -> Fixed {
-        if self.0 == 0 {
-            return self;
+impl<const FRAC: u32> Zero for FixedPoint<FRAC> {
+    fn zero() -> Self {
+        Self::ZERO
+    }
+
+    fn is_zero(&self) -> bool {
+        self.epsilons() == 0
+    }
+}
+
+impl<const FRAC: u32> One for FixedPoint<FRAC> {
+    fn one() -> Self {
+        Self::ONE
+    }
+}
+
+impl<const FRAC: u32> Num for FixedPoint<FRAC> {
+    type FromStrRadixErr = ParseFixedError;
+
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, ParseFixedError> {
+        assert!((2..=36).contains(&radix), "radix must be in the range 2..=36");
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (whole_digits, fraction_digits) = digits.split_once('.').unwrap_or((digits, ""));
+        if whole_digits.is_empty() && fraction_digits.is_empty() {
+            return Err(ParseFixedError::Empty);
         }
-        Fixed(self.0 ^ 0x80000000)
+        if !whole_digits.chars().chain(fraction_digits.chars()).all(|c| c.is_digit(radix)) {
+            return Err(ParseFixedError::InvalidDigit);
+        }
+        let mut whole: u64 = 0;
+        for c in whole_digits.chars() {
+            whole = whole * radix as u64 + c.to_digit(radix).unwrap() as u64;
+            if whole >= 2u64.pow(Self::WHOLE_BITS) {
+                return Err(ParseFixedError::Overflow);
+            }
+        }
+        let epsilons = (whole << FRAC) + parse_fraction_epsilons(fraction_digits, radix, FRAC);
+        let signed = if negative { -(epsilons as i64) } else { epsilons as i64 };
+        Self::checked_from_signed_epsilons(signed).ok_or(ParseFixedError::Overflow)
+    }
+}
+
+impl<const FRAC: u32> ToPrimitive for FixedPoint<FRAC> {
+    fn to_i64(&self) -> Option<i64> {
+        Some(self.signed_epsilons() / Self::DENOMINATOR as i64)
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        let truncated = self.signed_epsilons() / Self::DENOMINATOR as i64;
+        if truncated >= 0 { Some(truncated as u64) } else { None }
+    }
+
+    fn to_f32(&self) -> Option<f32> {
+        Some(Self::to_f32(self))
+    }
+
+    fn to_f64(&self) -> Option<f64> {
+        Some(self.signed_epsilons() as f64 / Self::DENOMINATOR as f64)
+    }
+}
+
+impl<const FRAC: u32> NumCast for FixedPoint<FRAC> {
+    fn from<T: ToPrimitive>(n: T) -> Option<Self> {
+        Self::from_f64(n.to_f64()?)
+    }
+}
+
+impl<const FRAC: u32> Bounded for FixedPoint<FRAC> {
+    fn min_value() -> Self {
+        Self::MIN
+    }
+
+    fn max_value() -> Self {
+        Self::MAX
+    }
+}
+
+impl<const FRAC: u32> Signed for FixedPoint<FRAC> {
+    fn abs(&self) -> Self {
+        Self::abs(*self)
+    }
+
+    fn abs_sub(&self, other: &Self) -> Self {
+        if *self <= *other { Self::ZERO } else { *self - *other }
+    }
+
+    fn signum(&self) -> Self {
+        Self::signum(*self)
+    }
+
+    fn is_positive(&self) -> bool {
+        !self.is_zero() && !self.is_negative()
+    }
+
+    fn is_negative(&self) -> bool {
+        Self::is_negative(self)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cmp::Ordering;
+
     use super::*;
 
+    use cgmath::InnerSpace;
+
     #[test]
     fn create_and_unpack() {
         assert_eq!((false, 128, 0), Fixed::new(128, 0).unpack());
@@ -195,6 +728,23 @@ mod tests {
         assert_eq!((true, 0, 128), Fixed::from_f32(-0.5).unpack());
     }
 
+    #[test]
+    fn from_f32_just_below_whole() {
+        assert_eq!(Fixed::new(1, 0), Fixed::from_f32(0.999));
+        assert_eq!(Fixed::new(-1, 0), Fixed::from_f32(-0.999));
+        assert_eq!(Fixed::new(11, 0), Fixed::from_f32(10.999));
+        assert_eq!(FixedFine::new(1, 0), FixedFine::from_f32(0.999996));
+        assert_eq!(Fixed::new(0, 255), Fixed::from_f32(0.997));
+    }
+
+    #[test]
+    fn checked_from_f32() {
+        assert_eq!(Some(Fixed::new(-3, 64)), Fixed::checked_from_f32(-3.25));
+        assert_eq!(None, FixedFine::checked_from_f32(40000.0));
+        assert_eq!(None, Fixed::checked_from_f32(f32::NAN));
+        assert_eq!(None, Fixed::checked_from_f32(f32::INFINITY));
+    }
+
     #[test]
     fn add() {
         assert_eq!(Fixed::new(3, 0), Fixed::new(1, 0) + Fixed::new(2, 0));
@@ -213,7 +763,7 @@ mod tests {
     #[test]
     fn epsilon() {
         let mut f = Fixed::ZERO;
-        for _ in 0..DENOMINATOR {
+        for _ in 0..Fixed::DENOMINATOR {
             f += Fixed::EPSILON;
         }
         assert_eq!((false, 1, 0), f.unpack());
@@ -309,8 +859,646 @@ mod tests {
     fn epsilons() {
         assert_eq!(0, Fixed::ZERO.epsilons());
         assert_eq!(1, Fixed::EPSILON.epsilons());
-        assert_eq!(DENOMINATOR, Fixed::new(1, 0).epsilons());
-        assert_eq!(DENOMINATOR, Fixed::new(-1, 0).epsilons());
-        assert_eq!(2 * DENOMINATOR + 64, Fixed::new(2, 64).epsilons());
+        assert_eq!(Fixed::DENOMINATOR, Fixed::new(1, 0).epsilons());
+        assert_eq!(Fixed::DENOMINATOR, Fixed::new(-1, 0).epsilons());
+        assert_eq!(2 * Fixed::DENOMINATOR + 64, Fixed::new(2, 64).epsilons());
+    }
+
+    #[test]
+    fn mul() {
+        assert_eq!(Fixed::new(6, 0), Fixed::new(2, 0) * Fixed::new(3, 0));
+        assert_eq!(Fixed::new(3, 192), Fixed::new(1, 128) * Fixed::new(2, 128));
+        assert_eq!(Fixed::new(-6, 0), Fixed::new(-2, 0) * Fixed::new(3, 0));
+        assert_eq!(Fixed::new(6, 0), Fixed::new(-2, 0) * Fixed::new(-3, 0));
+        assert_eq!(Fixed::ZERO, Fixed::new(-2, 0) * Fixed::ZERO);
+        assert_eq!(Fixed::new(0, 64), Fixed::new(0, 128) * Fixed::new(0, 128));
+    }
+
+    #[test]
+    fn mul_rounds_toward_zero() {
+        // 1/256 * 1/2 = 1/512, which isn't representable.
+        assert_eq!(Fixed::ZERO, Fixed::EPSILON * Fixed::new(0, 128));
+        assert_eq!(Fixed::ZERO, -Fixed::EPSILON * Fixed::new(0, 128));
+        // 3/256 * 3/4 = 2.25/256
+        assert_eq!(Fixed::new(0, 2), Fixed::new(0, 3) * Fixed::new(0, 192));
+        assert_eq!(-Fixed::new(0, 2), -Fixed::new(0, 3) * Fixed::new(0, 192));
+    }
+
+    #[test]
+    fn mul_assign() {
+        let mut f = Fixed::new(10, 0);
+        f *= Fixed::new(0, 192);
+        assert_eq!(Fixed::new(7, 128), f);
+
+        let mut f = Fixed::new(-1, 128);
+        f *= 3;
+        assert_eq!(Fixed::new(-4, 128), f);
+    }
+
+    #[test]
+    fn mul_integer() {
+        assert_eq!(Fixed::new(7, 128), Fixed::new(2, 128) * 3);
+        assert_eq!(Fixed::new(7, 128), 3 * Fixed::new(2, 128));
+        assert_eq!(Fixed::new(-7, 128), Fixed::new(2, 128) * -3);
+    }
+
+    #[test]
+    #[should_panic]
+    fn mul_overflow() {
+        let _ = Fixed::new(4096, 0) * Fixed::new(4096, 0);
+    }
+
+    #[test]
+    fn div() {
+        assert_eq!(Fixed::new(2, 0), Fixed::new(6, 0) / Fixed::new(3, 0));
+        assert_eq!(Fixed::new(1, 128), Fixed::new(3, 0) / Fixed::new(2, 0));
+        assert_eq!(Fixed::new(-1, 128), Fixed::new(3, 0) / Fixed::new(-2, 0));
+        assert_eq!(Fixed::new(1, 128), Fixed::new(-3, 0) / Fixed::new(-2, 0));
+        assert_eq!(Fixed::new(8, 0), Fixed::new(2, 0) / Fixed::new(0, 64));
+    }
+
+    #[test]
+    fn div_rounds_toward_zero() {
+        // 1/3 = 85.33/256
+        assert_eq!(Fixed::new(0, 85), Fixed::new(1, 0) / Fixed::new(3, 0));
+        assert_eq!(-Fixed::new(0, 85), Fixed::new(-1, 0) / Fixed::new(3, 0));
+        assert_eq!(Fixed::ZERO, Fixed::EPSILON / Fixed::new(2, 0));
+    }
+
+    #[test]
+    fn div_assign() {
+        let mut f = Fixed::new(10, 0);
+        f /= Fixed::new(4, 0);
+        assert_eq!(Fixed::new(2, 128), f);
+    }
+
+    #[test]
+    #[should_panic]
+    fn div_by_zero() {
+        let _ = Fixed::new(1, 0) / Fixed::ZERO;
+    }
+
+    #[test]
+    #[should_panic]
+    fn div_overflow() {
+        let _ = Fixed::new(1048576, 0) / Fixed::new(0, 16);
+    }
+
+    #[test]
+    fn rem() {
+        assert_eq!(Fixed::new(1, 0), Fixed::new(7, 0) % Fixed::new(3, 0));
+        assert_eq!(Fixed::new(0, 128), Fixed::new(2, 128) % Fixed::new(1, 0));
+        assert_eq!(Fixed::new(0, 64), Fixed::new(1, 64) % Fixed::new(0, 128));
+        // The result takes the sign of the dividend, like integer remainder.
+        assert_eq!(-Fixed::new(0, 128), Fixed::new(-2, 128) % Fixed::new(1, 0));
+        assert_eq!(Fixed::new(0, 128), Fixed::new(2, 128) % Fixed::new(-1, 0));
+        assert_eq!(Fixed::ZERO, Fixed::new(6, 0) % Fixed::new(2, 0));
+    }
+
+    #[test]
+    fn rem_assign() {
+        let mut f = Fixed::new(5, 32);
+        f %= Fixed::new(2, 0);
+        assert_eq!(Fixed::new(1, 32), f);
+    }
+
+    #[test]
+    fn partial_ord() {
+        assert!(Fixed::new(1, 0) < Fixed::new(2, 0));
+        assert!(Fixed::new(1, 10) > Fixed::new(1, 9));
+        assert!(Fixed::new(-1, 0) < Fixed::new(1, 0));
+        assert!(Fixed::new(-2, 0) < Fixed::new(-1, 0));
+        assert!(Fixed::new(-1, 128) < Fixed::new(-1, 0));
+        assert!(-Fixed::EPSILON < Fixed::ZERO);
+        assert!(Fixed::MIN < Fixed::MAX);
+        assert!(Fixed::new(3, 0) <= Fixed::new(3, 0));
+    }
+
+    #[test]
+    fn num_identities() {
+        assert_eq!(Fixed::ZERO, Fixed::zero());
+        assert!(Fixed::ZERO.is_zero());
+        assert!(!Fixed::EPSILON.is_zero());
+        assert_eq!(Fixed::new(1, 0), Fixed::one());
+        assert_eq!(Fixed::MIN, Fixed::min_value());
+        assert_eq!(Fixed::MAX, Fixed::max_value());
+        assert_eq!(Fixed::MIN, -Fixed::MAX);
+    }
+
+    #[test]
+    fn from_str_radix() {
+        assert_eq!(Ok(Fixed::new(2, 128)), Fixed::from_str_radix("2.5", 10));
+        assert_eq!(Ok(Fixed::new(-6, 64)), Fixed::from_str_radix("-6.25", 10));
+        assert_eq!(Ok(Fixed::new(12, 0)), Fixed::from_str_radix("+12", 10));
+        assert_eq!(Ok(Fixed::new(0, 128)), Fixed::from_str_radix(".5", 10));
+        assert_eq!(Ok(Fixed::new(3, 0)), Fixed::from_str_radix("3.", 10));
+        assert_eq!(Ok(Fixed::new(5, 192)), Fixed::from_str_radix("101.11", 2));
+        assert_eq!(Ok(Fixed::new(255, 8)), Fixed::from_str_radix("ff.08", 16));
+        // 0.1 is 25.6 epsilons, which rounds to 26, matching `from_f32`.
+        assert_eq!(Ok(Fixed::new(0, 26)), Fixed::from_str_radix("0.1", 10));
+        // Exactly half an epsilon rounds away from zero.
+        assert_eq!(Ok(Fixed::EPSILON), Fixed::from_str_radix("0.001953125", 10));
+        assert_eq!(Ok(-Fixed::EPSILON), Fixed::from_str_radix("-0.001953125", 10));
+        assert_eq!(Ok(Fixed::ZERO), Fixed::from_str_radix("0.001953124", 10));
+        assert_eq!(Ok(Fixed::new(1, 0)), Fixed::from_str_radix("0.999", 10));
+    }
+
+    #[test]
+    fn no_negative_zero() {
+        assert_eq!(Fixed::ZERO, Fixed::new(-1, 0) + Fixed::new(1, 0));
+        assert_eq!(Fixed::ZERO, Fixed::new(-1, 128) - Fixed::new(-1, 128));
+        assert_eq!(Fixed::ZERO, Fixed::from_parts(true, 0, 0));
+        assert_eq!(Fixed::ZERO, Fixed::from_f32(-0.001));
+        assert_eq!(Fixed::ZERO, -Fixed::new(0, 1) * Fixed::new(0, 1));
+        assert!(!(Fixed::new(-1, 0) + Fixed::ONE).is_negative());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!("2.5", Fixed::new(2, 128).to_string());
+        assert_eq!("-6.25", Fixed::new(-6, 64).to_string());
+        assert_eq!("0", Fixed::ZERO.to_string());
+        assert_eq!("12", Fixed::new(12, 0).to_string());
+        assert_eq!("0.00390625", Fixed::EPSILON.to_string());
+        assert_eq!("-8388607.99609375", Fixed::MIN.to_string());
+        assert_eq!("0.0000152587890625", FixedFine::EPSILON.to_string());
+
+        assert_eq!("2.50", format!("{:.2}", Fixed::new(2, 128)));
+        assert_eq!("3", format!("{:.0}", Fixed::new(2, 128)));
+        assert_eq!("-3", format!("{:.0}", Fixed::new(-2, 128)));
+        assert_eq!("1.00", format!("{:.2}", Fixed::new(0, 255)));
+        assert_eq!("0.0039062500", format!("{:.10}", Fixed::EPSILON));
+        assert_eq!(format!("0.00390625{}", "0".repeat(92)), format!("{:.100}", Fixed::EPSILON));
+        assert_eq!(format!("-2.5{}", "0".repeat(59)), format!("{:.60}", FixedFine::new(-2, 32768)));
+        assert_eq!("+2.5", format!("{:+}", Fixed::new(2, 128)));
+        assert_eq!("-002.5", format!("{:06}", Fixed::new(-2, 128)));
+        assert_eq!("  2.5", format!("{:>5}", Fixed::new(2, 128)));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for value in [Fixed::new(2, 128), Fixed::new(-6, 1), Fixed::MAX, Fixed::MIN, Fixed::EPSILON] {
+            assert_eq!(Ok(value), value.to_string().parse());
+        }
+        assert_eq!(Ok(FixedFine::new(-1, 12345)), FixedFine::new(-1, 12345).to_string().parse());
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(Ok(Fixed::new(2, 128)), "2.5".parse());
+        assert_eq!(Ok(Fixed::new(-1, 26)), "-1.1".parse());
+        assert_eq!(Err(ParseFixedError::InvalidDigit), "2.5f".parse::<Fixed>());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        assert_eq!("-640", serde_json::to_string(&Fixed::new(-2, 128)).unwrap());
+        assert_eq!(Fixed::new(-2, 128), serde_json::from_str("-640").unwrap());
+        let v = vec3(Fixed::MAX, Fixed::MIN, Fixed::EPSILON);
+        assert_eq!(v, serde_json::from_str(&serde_json::to_string(&v).unwrap()).unwrap());
+        assert!(serde_json::from_str::<Fixed>(&i32::MIN.to_string()).is_err());
+        assert!(serde_json::from_str::<Fixed>("2.5").is_err());
+    }
+
+    #[test]
+    fn from_str_radix_errors() {
+        assert_eq!(Err(ParseFixedError::Empty), Fixed::from_str_radix("", 10));
+        assert_eq!(Err(ParseFixedError::Empty), Fixed::from_str_radix("-.", 10));
+        assert_eq!(Err(ParseFixedError::InvalidDigit), Fixed::from_str_radix("1.2.3", 10));
+        assert_eq!(Err(ParseFixedError::InvalidDigit), Fixed::from_str_radix("1e5", 10));
+        assert_eq!(Err(ParseFixedError::InvalidDigit), Fixed::from_str_radix(" 1", 10));
+        assert_eq!(Err(ParseFixedError::Overflow), Fixed::from_str_radix("8388608", 10));
+        assert_eq!(Err(ParseFixedError::Overflow), Fixed::from_str_radix("8388607.999", 10));
+        assert_eq!(Ok(Fixed::new(8388607, 0)), Fixed::from_str_radix("8388607", 10));
+    }
+
+    #[test]
+    fn num_cast() {
+        assert_eq!(Some(Fixed::new(3, 0)), <Fixed as NumCast>::from(3i32));
+        assert_eq!(Some(Fixed::new(-2, 128)), <Fixed as NumCast>::from(-2.5f32));
+        assert_eq!(None, <Fixed as NumCast>::from(1e9f64));
+        assert_eq!(None, <Fixed as NumCast>::from(f32::NAN));
+        assert_eq!(Some(2), Fixed::new(2, 200).to_i32());
+        assert_eq!(Some(-2), Fixed::new(-2, 200).to_i32());
+        assert_eq!(None, Fixed::new(-2, 0).to_u32());
+        assert_eq!(Some(0), (-Fixed::new(0, 128)).to_u32());
+        assert_eq!(Some(-2.5), Fixed::new(-2, 128).to_f64());
+    }
+
+    #[test]
+    fn signed() {
+        assert_eq!(Fixed::new(2, 128), Signed::abs(&Fixed::new(-2, 128)));
+        assert_eq!(Fixed::new(2, 128), Signed::abs(&Fixed::new(2, 128)));
+        assert_eq!(Fixed::new(1, 0), Fixed::new(3, 0).abs_sub(&Fixed::new(2, 0)));
+        assert_eq!(Fixed::ZERO, Fixed::new(2, 0).abs_sub(&Fixed::new(3, 0)));
+        assert_eq!(-Fixed::ONE, Signed::signum(&Fixed::new(-4, 0)));
+        assert_eq!(Fixed::ZERO, Signed::signum(&Fixed::ZERO));
+        assert_eq!(Fixed::ONE, Signed::signum(&Fixed::new(0, 1)));
+        assert!(Fixed::EPSILON.is_positive());
+        assert!(!Fixed::ZERO.is_positive());
+        assert!(Signed::is_negative(&-Fixed::EPSILON));
+    }
+
+    #[test]
+    fn cgmath_vector_math() {
+        let v1 = vec3(Fixed::new(1, 0), Fixed::new(2, 0), Fixed::new(-3, 0));
+        let v2 = vec3(Fixed::new(0, 128), Fixed::new(-1, 0), Fixed::new(2, 0));
+        assert_eq!(vec3(Fixed::new(1, 128), Fixed::new(1, 0), Fixed::new(-1, 0)), v1 + v2);
+        assert_eq!(vec3(Fixed::new(0, 128), Fixed::new(3, 0), Fixed::new(-5, 0)), v1 - v2);
+        assert_eq!(vec3(Fixed::new(2, 0), Fixed::new(4, 0), Fixed::new(-6, 0)), v1 * Fixed::new(2, 0));
+        assert_eq!(vec3(Fixed::new(0, 128), Fixed::new(1, 0), Fixed::new(-1, 128)), v1 / Fixed::new(2, 0));
+        assert_eq!(Fixed::new(-7, 128), v1.dot(v2));
+        assert_eq!(Fixed::new(14, 0), v1.magnitude2());
+
+        let p = point3(Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO);
+        assert_eq!(point3(Fixed::new(1, 128), Fixed::new(-1, 0), Fixed::new(2, 0)), p + v2);
+        assert_eq!(v2, (p + v2) - p);
+    }
+
+    #[test]
+    fn sqrt() {
+        assert_eq!(Fixed::ZERO, Fixed::ZERO.sqrt());
+        assert_eq!(Fixed::new(2, 0), Fixed::new(4, 0).sqrt());
+        assert_eq!(Fixed::new(0, 128), Fixed::new(0, 64).sqrt());
+        assert_eq!(Fixed::new(12, 0), Fixed::new(144, 0).sqrt());
+        // sqrt(2) = 362.04/256
+        assert_eq!(Fixed::new(1, 106), Fixed::new(2, 0).sqrt());
+        assert_eq!(Fixed::new(2896, 79), Fixed::MAX.sqrt());
+    }
+
+    #[test]
+    #[should_panic]
+    fn sqrt_negative() {
+        Fixed::new(-1, 0).sqrt();
+    }
+
+    #[test]
+    fn sin_cos() {
+        let half_pi = Fixed::pi() / Fixed::new(2, 0);
+        assert_eq!(Fixed::ZERO, Fixed::ZERO.sin());
+        assert_eq!(Fixed::ONE, Fixed::ZERO.cos());
+        assert_eq!(Fixed::ONE, half_pi.sin());
+        assert_eq!(-Fixed::ONE, (-half_pi).sin());
+        assert_eq!(Fixed::ZERO, Fixed::pi().sin());
+        assert_eq!(-Fixed::ONE, Fixed::pi().cos());
+        // sin(0.5) = 122.73/256
+        assert_eq!(Fixed::new(0, 123), Fixed::new(0, 128).sin());
+        assert_eq!(-Fixed::new(0, 123), (-Fixed::new(0, 128)).sin());
+    }
+
+    #[test]
+    fn sin_cos_match_f64() {
+        for epsilons in (-20000..20000).step_by(7) {
+            let angle = Fixed::from_signed_epsilons(epsilons);
+            let angle_f64 = epsilons as f64 / Fixed::DENOMINATOR as f64;
+            let sin_error = (angle.sin().to_f32() as f64 - angle_f64.sin()).abs();
+            let cos_error = (angle.cos().to_f32() as f64 - angle_f64.cos()).abs();
+            assert!(sin_error <= 0.5 / Fixed::DENOMINATOR as f64 + 1e-6, "sin({:?}) is off by {}", angle, sin_error);
+            assert!(cos_error <= 0.5 / Fixed::DENOMINATOR as f64 + 1e-6, "cos({:?}) is off by {}", angle, cos_error);
+        }
+    }
+
+    #[test]
+    fn atan2() {
+        let one = Fixed::ONE;
+        assert_eq!(Fixed::ZERO, Fixed::ZERO.atan2(Fixed::ZERO));
+        assert_eq!(Fixed::ZERO, Fixed::ZERO.atan2(one));
+        assert_eq!(Fixed::pi(), Fixed::ZERO.atan2(-one));
+        // pi/4 = 201.06/256
+        assert_eq!(Fixed::new(0, 201), one.atan2(one));
+        assert_eq!(-Fixed::new(0, 201), (-one).atan2(one));
+        assert_eq!(Fixed::new(2, 91), one.atan2(-one));
+        assert_eq!(-Fixed::new(2, 91), (-one).atan2(-one));
+        assert_eq!(Fixed::new(1, 146), one.atan2(Fixed::ZERO));
+    }
+
+    #[test]
+    fn atan2_matches_f64() {
+        for y in (-3000..3000).step_by(37) {
+            for x in (-3000..3000).step_by(41) {
+                let (yf, xf) = (Fixed::from_signed_epsilons(y), Fixed::from_signed_epsilons(x));
+                let error = (yf.atan2(xf).to_f32() as f64 - (y as f64).atan2(x as f64)).abs();
+                assert!(error <= 0.5 / Fixed::DENOMINATOR as f64 + 1e-6, "atan2({:?}, {:?}) is off by {}", yf, xf, error);
+            }
+        }
+    }
+
+    #[test]
+    fn vector3_normalize() {
+        let v = vec3(Fixed::new(3, 0), Fixed::ZERO, Fixed::new(-4, 0));
+        assert_eq!(Fixed::new(5, 0), Fixed::vector3_magnitude(v));
+        assert_eq!(vec3(Fixed::new(0, 153), Fixed::ZERO, -Fixed::new(0, 204)), Fixed::vector3_normalize(v));
+
+        let v = vec3(Fixed::ZERO, Fixed::new(0, 3), Fixed::ZERO);
+        assert_eq!(vec3(Fixed::ZERO, Fixed::ONE, Fixed::ZERO), Fixed::vector3_normalize(v));
+
+        assert_eq!(Fixed::ZERO_VECTOR, Fixed::vector3_normalize(Fixed::ZERO_VECTOR));
+
+        // Large vectors don't overflow while computing the magnitude.
+        let v = vec3(Fixed::new(8000, 0), Fixed::new(8000, 0), Fixed::ZERO);
+        assert_eq!(Fixed::new(11313, 181), Fixed::vector3_magnitude(v));
+        assert_eq!(vec3(Fixed::new(0, 181), Fixed::new(0, 181), Fixed::ZERO), Fixed::vector3_normalize(v));
+    }
+
+    #[test]
+    fn ord() {
+        let mut values = vec![Fixed::new(2, 0), Fixed::new(-1, 128), Fixed::ZERO, Fixed::new(-1, 0), Fixed::EPSILON, -Fixed::EPSILON];
+        values.sort();
+        assert_eq!(vec![Fixed::new(-1, 128), Fixed::new(-1, 0), -Fixed::EPSILON, Fixed::ZERO, Fixed::EPSILON, Fixed::new(2, 0)], values);
+        assert_eq!(Ordering::Less, Fixed::MIN.cmp(&Fixed::MAX));
+    }
+
+    #[test]
+    fn min_max_clamp() {
+        assert_eq!(Fixed::new(-3, 0), Fixed::new(-3, 0).min(Fixed::new(2, 0)));
+        assert_eq!(Fixed::new(2, 0), Fixed::new(-3, 0).max(Fixed::new(2, 0)));
+        assert_eq!(Fixed::new(-1, 0), Fixed::new(-3, 0).clamp(Fixed::new(-1, 0), Fixed::new(1, 0)));
+        assert_eq!(Fixed::new(0, 5), Fixed::new(0, 5).clamp(Fixed::new(-1, 0), Fixed::new(1, 0)));
+        assert_eq!(Fixed::new(1, 0), Fixed::new(1, 1).clamp(Fixed::new(-1, 0), Fixed::new(1, 0)));
+    }
+
+    #[test]
+    fn abs_signum() {
+        assert_eq!(Fixed::new(2, 128), Fixed::new(-2, 128).abs());
+        assert_eq!(Fixed::new(2, 128), Fixed::new(2, 128).abs());
+        assert_eq!(Fixed::ZERO, Fixed::ZERO.abs());
+        assert_eq!(Fixed::MAX, Fixed::MIN.abs());
+        assert_eq!(-Fixed::ONE, Fixed::new(-4, 0).signum());
+        assert_eq!(Fixed::ZERO, Fixed::ZERO.signum());
+        assert_eq!(Fixed::ONE, Fixed::EPSILON.signum());
+    }
+
+    #[test]
+    fn rounding() {
+        // (value, floor, ceil, round, trunc)
+        let cases = [
+            (Fixed::new(2, 0), 2, 2, 2, 2),
+            (Fixed::new(2, 64), 2, 3, 2, 2),
+            (Fixed::new(2, 128), 2, 3, 3, 2),
+            (Fixed::new(2, 200), 2, 3, 3, 2),
+            (Fixed::new(-2, 0), -2, -2, -2, -2),
+            (Fixed::new(-2, 64), -3, -2, -2, -2),
+            (Fixed::new(-2, 128), -3, -2, -3, -2),
+            (Fixed::new(-2, 200), -3, -2, -3, -2),
+            (-Fixed::EPSILON, -1, 0, 0, 0),
+            (Fixed::ZERO, 0, 0, 0, 0),
+        ];
+        for &(value, floor, ceil, round, trunc) in cases.iter() {
+            assert_eq!(floor, value.floor_i32(), "floor of {:?}", value);
+            assert_eq!(ceil, value.ceil_i32(), "ceil of {:?}", value);
+            assert_eq!(round, value.round_i32(), "round of {:?}", value);
+            assert_eq!(trunc, value.trunc_i32(), "trunc of {:?}", value);
+            assert_eq!(Fixed::new(floor, 0), value.floor());
+            assert_eq!(Fixed::new(ceil, 0), value.ceil());
+            assert_eq!(Fixed::new(round, 0), value.round());
+            assert_eq!(Fixed::new(trunc, 0), value.trunc());
+        }
+        assert_eq!(8388607, Fixed::MAX.floor_i32());
+        assert_eq!(8388608, Fixed::MAX.ceil_i32());
+        assert_eq!(-8388608, Fixed::MIN.floor_i32());
+    }
+
+    #[test]
+    fn checked() {
+        assert_eq!(Some(Fixed::new(3, 0)), Fixed::new(1, 0).checked_add(Fixed::new(2, 0)));
+        assert_eq!(None, Fixed::MAX.checked_add(Fixed::EPSILON));
+        assert_eq!(None, Fixed::MIN.checked_add(-Fixed::EPSILON));
+        assert_eq!(Some(Fixed::ZERO), Fixed::MIN.checked_add(Fixed::MAX));
+
+        assert_eq!(Some(Fixed::new(-1, 0)), Fixed::new(1, 0).checked_sub(Fixed::new(2, 0)));
+        assert_eq!(None, Fixed::MIN.checked_sub(Fixed::EPSILON));
+        assert_eq!(None, Fixed::MAX.checked_sub(-Fixed::EPSILON));
+
+        assert_eq!(Some(Fixed::new(3, 192)), Fixed::new(1, 128).checked_mul(Fixed::new(2, 128)));
+        assert_eq!(None, Fixed::new(4096, 0).checked_mul(Fixed::new(4096, 0)));
+        assert_eq!(None, Fixed::new(-4096, 0).checked_mul(Fixed::new(4096, 0)));
+
+        assert_eq!(Some(Fixed::new(1, 128)), Fixed::new(3, 0).checked_div(Fixed::new(2, 0)));
+        assert_eq!(None, Fixed::new(3, 0).checked_div(Fixed::ZERO));
+        assert_eq!(None, Fixed::MAX.checked_div(Fixed::new(0, 128)));
+    }
+
+    #[test]
+    fn saturating() {
+        assert_eq!(Fixed::new(3, 0), Fixed::new(1, 0).saturating_add(Fixed::new(2, 0)));
+        assert_eq!(Fixed::MAX, Fixed::MAX.saturating_add(Fixed::EPSILON));
+        assert_eq!(Fixed::MAX, Fixed::new(8000000, 0).saturating_add(Fixed::new(8000000, 0)));
+        assert_eq!(Fixed::MIN, Fixed::MIN.saturating_add(-Fixed::EPSILON));
+
+        assert_eq!(Fixed::MIN, Fixed::MIN.saturating_sub(Fixed::EPSILON));
+        assert_eq!(Fixed::MAX, Fixed::MAX.saturating_sub(Fixed::MIN));
+        assert_eq!(Fixed::new(-1, 0), Fixed::new(1, 0).saturating_sub(Fixed::new(2, 0)));
+
+        assert_eq!(Fixed::MAX, Fixed::new(4096, 0).saturating_mul(Fixed::new(4096, 0)));
+        assert_eq!(Fixed::MIN, Fixed::new(-4096, 0).saturating_mul(Fixed::new(4096, 0)));
+        assert_eq!(Fixed::new(-3, 192), Fixed::new(-1, 128).saturating_mul(Fixed::new(2, 128)));
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(Fixed::new(3, 0), Fixed::new(1, 0).wrapping_add(Fixed::new(2, 0)));
+        assert_eq!(Fixed::MIN, Fixed::MAX.wrapping_add(Fixed::EPSILON));
+        assert_eq!(Fixed::MIN + Fixed::EPSILON, Fixed::MAX.wrapping_add(Fixed::new(0, 2)));
+        assert_eq!(Fixed::MAX, Fixed::MIN.wrapping_sub(Fixed::EPSILON));
+        assert_eq!(Fixed::MAX - Fixed::EPSILON, Fixed::MIN.wrapping_add(-Fixed::new(0, 2)));
+        assert_eq!(Fixed::new(-1, 0), Fixed::new(1, 0).wrapping_sub(Fixed::new(2, 0)));
+        // 4096 * 4096 is 2^32 epsilons, which is one more than a full lap of 2^32 - 1 values.
+        assert_eq!(Fixed::EPSILON, Fixed::new(4096, 0).wrapping_mul(Fixed::new(4096, 0)));
+        assert_eq!(-Fixed::EPSILON, Fixed::new(-4096, 0).wrapping_mul(Fixed::new(4096, 0)));
+    }
+
+    #[test]
+    fn fine_precision() {
+        assert_eq!(65536, FixedFine::DENOMINATOR);
+        assert_eq!(15, FixedFine::WHOLE_BITS);
+        assert_eq!(FixedFine::new(32767, 65535), FixedFine::MAX);
+        assert_eq!(FixedFine::new(3, 49152), FixedFine::new(1, 32768) * FixedFine::new(2, 32768));
+        assert_eq!(FixedFine::new(0, 1), FixedFine::new(0, 256) * FixedFine::new(0, 256));
+        assert_eq!(FixedFine::ZERO, FixedFine::new(0, 255) * FixedFine::new(0, 255));
+        assert_eq!(-FixedFine::new(1, 32768), FixedFine::new(-3, 0) / FixedFine::new(2, 0));
+        assert_eq!(FixedFine::new(1, 0), FixedFine::new(0, 65535) + FixedFine::EPSILON);
+        assert_eq!(1.25, FixedFine::from_f32(1.25).to_f32());
+        assert_eq!("Fixed(-2.00128)", format!("{:?}", FixedFine::new(-2, 128)));
+        assert_eq!(FixedFine::new(1, 0), FixedFine::new(2, 0).sin().abs().ceil());
+    }
+
+    #[test]
+    fn convert() {
+        let fine: FixedFine = Fixed::new(-2, 128).convert();
+        assert_eq!(FixedFine::new(-2, 32768), fine);
+        assert_eq!(Fixed::new(-2, 128), fine.convert());
+
+        // Extra fraction bits truncate toward zero.
+        assert_eq!(Fixed::new(1, 0), FixedFine::new(1, 255).convert());
+        assert_eq!(Fixed::new(-1, 0), FixedFine::new(-1, 255).convert());
+        assert_eq!(Fixed::ZERO, FixedFine::EPSILON.convert());
+
+        assert_eq!(Some(FixedFine::new(32767, 0)), Fixed::new(32767, 0).checked_convert());
+        assert_eq!(None, Fixed::new(32768, 0).checked_convert::<16>());
+        assert_eq!(None, Fixed::MIN.checked_convert::<16>());
+        assert_eq!(Some(Fixed::new(32767, 255)), FixedFine::MAX.checked_convert());
+    }
+
+    #[test]
+    #[should_panic]
+    fn convert_overflow() {
+        let _: FixedFine = Fixed::new(40000, 0).convert();
+    }
+}
+
+// Checks the operators against a reference model that does the same arithmetic on plain i64
+// epsilon counts. Values only go in and out through `from_parts` and `unpack`, so the model shares
+// no code with the arithmetic it checks. Results are compared with `==` on the raw bits, which also
+// catches a negative zero.
+#[cfg(test)]
+mod reference_tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const MAX_EPSILONS: i64 = 0x7fffffff;
+
+    type UnaryOp<const FRAC: u32> = fn(FixedPoint<FRAC>) -> FixedPoint<FRAC>;
+
+    fn from_model<const FRAC: u32>(epsilons: i64) -> FixedPoint<FRAC> {
+        let magnitude = epsilons.unsigned_abs() as u32;
+        FixedPoint::from_parts(epsilons < 0, magnitude >> FRAC, magnitude & ((1 << FRAC) - 1))
+    }
+
+    fn to_model<const FRAC: u32>(value: FixedPoint<FRAC>) -> i64 {
+        let (negative, whole, fraction) = value.unpack();
+        let magnitude = ((whole as i64) << FRAC) | fraction as i64;
+        if negative { -magnitude } else { magnitude }
+    }
+
+    fn fits(epsilons: i64) -> Option<i64> {
+        if epsilons.abs() <= MAX_EPSILONS { Some(epsilons) } else { None }
+    }
+
+    fn check_unary<const FRAC: u32>(a: i64) {
+        let x = from_model::<FRAC>(a);
+        let one = 1i64 << FRAC;
+        assert_eq!(a, to_model(x));
+        assert_eq!(from_model::<FRAC>(-a), -x);
+        assert_eq!(from_model::<FRAC>(a.abs()), x.abs());
+        assert_eq!(from_model::<FRAC>(a.signum() * one), x.signum());
+
+        let floor = a.div_euclid(one);
+        let ceil = -(-a).div_euclid(one);
+        let trunc = a / one;
+        let round = a.signum() * ((a.abs() + one / 2) / one);
+        assert_eq!((floor, ceil, trunc, round), (x.floor_i32() as i64, x.ceil_i32() as i64, x.trunc_i32() as i64, x.round_i32() as i64));
+        let rounding: [(i64, UnaryOp<FRAC>); 4] =
+            [(floor, FixedPoint::floor), (ceil, FixedPoint::ceil), (trunc, FixedPoint::trunc), (round, FixedPoint::round)];
+        for (whole, function) in rounding {
+            if let Some(expected) = fits(whole * one) {
+                assert_eq!(from_model::<FRAC>(expected), function(x), "{} rounding to {}", a, whole);
+            }
+        }
+
+        if a >= 0 {
+            assert_eq!(from_model::<FRAC>(((a as u64) << FRAC).isqrt() as i64), x.sqrt());
+        }
+        assert_eq!(Ok(x), x.to_string().parse());
+    }
+
+    fn check_binary<const FRAC: u32>(a: i64, b: i64) {
+        let (x, y) = (from_model::<FRAC>(a), from_model::<FRAC>(b));
+        let one = 1i64 << FRAC;
+        assert_eq!(a.cmp(&b), x.cmp(&y));
+        assert_eq!(a == b, x == y);
+
+        let sum = fits(a + b);
+        let difference = fits(a - b);
+        let product = fits(a * b / one);
+        let quotient = if b == 0 { None } else { fits(a * one / b) };
+        for (name, expected, checked) in [
+            ("+", sum, x.checked_add(y)),
+            ("-", difference, x.checked_sub(y)),
+            ("*", product, x.checked_mul(y)),
+            ("/", quotient, x.checked_div(y)),
+        ] {
+            assert_eq!(expected.map(from_model::<FRAC>), checked, "{} {} {}", a, name, b);
+        }
+        if let Some(sum) = sum {
+            assert_eq!(from_model::<FRAC>(sum), x + y, "{} + {}", a, b);
+        }
+        if let Some(difference) = difference {
+            assert_eq!(from_model::<FRAC>(difference), x - y, "{} - {}", a, b);
+        }
+        if let Some(product) = product {
+            assert_eq!(from_model::<FRAC>(product), x * y, "{} * {}", a, b);
+        }
+        if let Some(quotient) = quotient {
+            assert_eq!(from_model::<FRAC>(quotient), x / y, "{} / {}", a, b);
+        }
+        if b != 0 {
+            assert_eq!(from_model::<FRAC>(a % b), x % y, "{} % {}", a, b);
+        }
+
+        let saturate = |value: i64| from_model::<FRAC>(value.clamp(-MAX_EPSILONS, MAX_EPSILONS));
+        assert_eq!(saturate(a + b), x.saturating_add(y));
+        assert_eq!(saturate(a - b), x.saturating_sub(y));
+        assert_eq!(saturate(a * b / one), x.saturating_mul(y));
+        let wrap = |value: i64| from_model::<FRAC>((value + MAX_EPSILONS).rem_euclid(2 * MAX_EPSILONS + 1) - MAX_EPSILONS);
+        assert_eq!(wrap(a + b), x.wrapping_add(y));
+        assert_eq!(wrap(a - b), x.wrapping_sub(y));
+        assert_eq!(wrap(a * b / one), x.wrapping_mul(y));
+    }
+
+    // Every pair within +-1.5, plus each of those against values at the whole-number boundaries
+    // and the ends of the range.
+    #[test]
+    fn exhaustive() {
+        let small: Vec<i64> = (-384..=384).collect();
+        let mut edges: Vec<i64> = vec![MAX_EPSILONS, MAX_EPSILONS - 1, MAX_EPSILONS - 255, MAX_EPSILONS - 256];
+        edges.extend([255, 256, 257, 511, 512, 4096 * 256, 46341 * 16]);
+        edges.extend(edges.clone().iter().map(|e| -e));
+        for &a in &small {
+            check_unary::<8>(a);
+            for &b in &small {
+                check_binary::<8>(a, b);
+            }
+        }
+        for &a in &edges {
+            check_unary::<8>(a);
+            for &b in small.iter().chain(&edges) {
+                check_binary::<8>(a, b);
+                check_binary::<8>(b, a);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn unary(a in -MAX_EPSILONS..=MAX_EPSILONS) {
+            check_unary::<8>(a);
+            check_unary::<16>(a);
+        }
+
+        #[test]
+        fn binary(a in -MAX_EPSILONS..=MAX_EPSILONS, b in -MAX_EPSILONS..=MAX_EPSILONS) {
+            check_binary::<8>(a, b);
+            check_binary::<16>(a, b);
+        }
+
+        // Products and quotients of full-range values almost always overflow, so also try operands
+        // near one.
+        #[test]
+        fn binary_small(a in -MAX_EPSILONS..=MAX_EPSILONS, b in -0x20000i64..=0x20000) {
+            check_binary::<8>(a, b);
+            check_binary::<8>(b, a);
+            check_binary::<16>(a, b);
+            check_binary::<16>(b, a);
+        }
+
+        #[test]
+        fn convert(a in -MAX_EPSILONS..=MAX_EPSILONS) {
+            let coarse = from_model::<8>(a);
+            assert_eq!(fits(a << 8).map(from_model::<16>), coarse.checked_convert::<16>());
+            let fine = from_model::<16>(a);
+            assert_eq!(from_model::<8>(a / 256), fine.convert::<8>());
+        }
     }
 }
@@ -1,18 +1,41 @@
-use cgmath::{InnerSpace, Point3, point3, Vector2, vec2, Vector3, vec3};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, point3, Vector2, vec2, Vector3, vec3};
+use winit::event::MouseButton;
 use winit::keyboard::KeyCode;
 
+use crate::atlas::{AtlasRect, TextureAtlas};
 use crate::camera::Camera;
-use crate::ecosim::{EcosimEntity, ecosim_tick};
-use crate::fixed_point::Fixed;
-use crate::render_util::Vertex;
-use crate::physics_world::{PhysicsBody, PhysicsConfig, physics_tick};
-use crate::voxel::{CHUNK_SIZE, VoxelChunk, VOXEL_SCALE};
+use crate::chunk_streaming::{ChunkStreamer, StreamingConfig};
+use crate::ecosim::{EcosimEntity, ecosim_tick, kill_entities_at};
+use crate::fixed_point::{Fixed, FixedFine};
+use crate::lighting::{light_new_chunk, relight_voxel};
+use crate::material::{AIR, DIRT, LAMP, SAND, STONE};
+use crate::render_util::{LineVertex, Vertex};
+use crate::physics_world::{BoundaryPolicy, OverflowBehavior, PhysicsBody, PhysicsConfig, PhysicsEvent, physics_tick};
+use crate::raycast::{RaycastOptions, VoxelHit, raycast_entities, raycast_voxels_f32};
+use crate::terrain::{NoiseTerrain, TerrainGenerator};
+use crate::voxel::{ChunkMesh, VOXEL_SCALE, VoxelType};
+use crate::voxel_world::{VoxelWorld, voxel_to_chunk};
 use crate::window::InputState;
 
 const PHYSICS_SECONDS_PER_TICK: f64 = 1.0 / 60.0;
 
 const ECOSIM_SECONDS_PER_TICK: f64 = 1.0 / 4.0;
 
+/// How far away, in voxels, the player can break and place blocks.
+const PLAYER_REACH: i32 = 8;
+
+/// Players that fall this far below the ground respawn.
+const KILL_DEPTH: i32 = -64;
+
+const SAVE_DIR: &str = "saves";
+
+/// What the player can place, picked with the number keys.
+const PLACEABLE_VOXELS: [VoxelType; 4] = [DIRT, STONE, SAND, LAMP];
+
 struct FirstPersonCameraController {
     pitch: f32,
     yaw: f32,
@@ -89,8 +112,9 @@ fn calc_normal(p0: [f32; 3], p1: [f32; 3], p2: [f32; 3]) -> [f32; 3] {
 }
 
 // Function written by Claude, cleaned up by me
-fn create_pyramid_mesh(offset: Point3<f32>, base_size: f32, height: f32) -> Vec<Vertex> {
+fn create_pyramid_mesh(offset: Point3<f32>, base_size: f32, height: f32, atlas_rect: [f32; 4]) -> Vec<Vertex> {
     const YELLOW: [f32; 3] = [1.0, 1.0, 0.0];
+    const FACE_COLOR: [f32; 3] = [1.0, 0.8156863, 0.5019608];
     let half_base = base_size / 2.0;
 
     // Define the 5 vertex positions
@@ -105,36 +129,36 @@ fn create_pyramid_mesh(offset: Point3<f32>, base_size: f32, height: f32) -> Vec<
 
     // Front face
     let front_normal = calc_normal(base_v0_pos, apex_pos, base_v1_pos);
-    let front_v0 = Vertex { position: base_v0_pos, light: YELLOW, uv: [0.0, 0.0], normal: front_normal };
-    let front_apex = Vertex { position: apex_pos, light: YELLOW, uv: [0.5, 0.5], normal: front_normal };
-    let front_v1 = Vertex { position: base_v1_pos, light: YELLOW, uv: [1.0, 0.0], normal: front_normal };
+    let front_v0 = Vertex { position: base_v0_pos, light: YELLOW, uv: [0.0, 0.0], normal: front_normal, color: FACE_COLOR, atlas_rect };
+    let front_apex = Vertex { position: apex_pos, light: YELLOW, uv: [0.5, 0.5], normal: front_normal, color: FACE_COLOR, atlas_rect };
+    let front_v1 = Vertex { position: base_v1_pos, light: YELLOW, uv: [1.0, 0.0], normal: front_normal, color: FACE_COLOR, atlas_rect };
 
     // Right face
     let right_normal = calc_normal(base_v1_pos, apex_pos, base_v2_pos);
-    let right_v1 = Vertex { position: base_v1_pos, light: YELLOW, uv: [0.0, 0.0], normal: right_normal };
-    let right_apex = Vertex { position: apex_pos, light: YELLOW, uv: [0.5, 0.5], normal: right_normal };
-    let right_v2 = Vertex { position: base_v2_pos, light: YELLOW, uv: [1.0, 0.0], normal: right_normal };
+    let right_v1 = Vertex { position: base_v1_pos, light: YELLOW, uv: [0.0, 0.0], normal: right_normal, color: FACE_COLOR, atlas_rect };
+    let right_apex = Vertex { position: apex_pos, light: YELLOW, uv: [0.5, 0.5], normal: right_normal, color: FACE_COLOR, atlas_rect };
+    let right_v2 = Vertex { position: base_v2_pos, light: YELLOW, uv: [1.0, 0.0], normal: right_normal, color: FACE_COLOR, atlas_rect };
 
     // Back face
     let back_normal = calc_normal(base_v2_pos, apex_pos, base_v3_pos);
-    let back_v2 = Vertex { position: base_v2_pos, light: YELLOW, uv: [0.0, 0.0], normal: back_normal };
-    let back_apex = Vertex { position: apex_pos, light: YELLOW, uv: [0.5, 0.5], normal: back_normal };
-    let back_v3 = Vertex { position: base_v3_pos, light: YELLOW, uv: [1.0, 0.0], normal: back_normal };
+    let back_v2 = Vertex { position: base_v2_pos, light: YELLOW, uv: [0.0, 0.0], normal: back_normal, color: FACE_COLOR, atlas_rect };
+    let back_apex = Vertex { position: apex_pos, light: YELLOW, uv: [0.5, 0.5], normal: back_normal, color: FACE_COLOR, atlas_rect };
+    let back_v3 = Vertex { position: base_v3_pos, light: YELLOW, uv: [1.0, 0.0], normal: back_normal, color: FACE_COLOR, atlas_rect };
 
     // Left face
     let left_normal = calc_normal(base_v3_pos, apex_pos, base_v0_pos);
-    let left_v3 = Vertex { position: base_v3_pos, light: YELLOW, uv: [0.0, 0.0], normal: left_normal };
-    let left_apex = Vertex { position: apex_pos, light: YELLOW, uv: [0.5, 0.5], normal: left_normal };
-    let left_v0 = Vertex { position: base_v0_pos, light: YELLOW, uv: [1.0, 0.0], normal: left_normal };
+    let left_v3 = Vertex { position: base_v3_pos, light: YELLOW, uv: [0.0, 0.0], normal: left_normal, color: FACE_COLOR, atlas_rect };
+    let left_apex = Vertex { position: apex_pos, light: YELLOW, uv: [0.5, 0.5], normal: left_normal, color: FACE_COLOR, atlas_rect };
+    let left_v0 = Vertex { position: base_v0_pos, light: YELLOW, uv: [1.0, 0.0], normal: left_normal, color: FACE_COLOR, atlas_rect };
 
     // Base (two triangles) - normal points downward
     let base_normal = calc_normal(base_v0_pos, base_v1_pos, base_v2_pos);
-    let base1_v0 = Vertex { position: base_v0_pos, light: YELLOW, uv: [0.0, 0.0], normal: base_normal };
-    let base1_v1 = Vertex { position: base_v1_pos, light: YELLOW, uv: [1.0, 0.0], normal: base_normal };
-    let base1_v2 = Vertex { position: base_v2_pos, light: YELLOW, uv: [1.0, 1.0], normal: base_normal };
-    let base2_v0 = Vertex { position: base_v0_pos, light: YELLOW, uv: [0.0, 0.0], normal: base_normal };
-    let base2_v2 = Vertex { position: base_v2_pos, light: YELLOW, uv: [1.0, 1.0], normal: base_normal };
-    let base2_v3 = Vertex { position: base_v3_pos, light: YELLOW, uv: [0.0, 1.0], normal: base_normal };
+    let base1_v0 = Vertex { position: base_v0_pos, light: YELLOW, uv: [0.0, 0.0], normal: base_normal, color: FACE_COLOR, atlas_rect };
+    let base1_v1 = Vertex { position: base_v1_pos, light: YELLOW, uv: [1.0, 0.0], normal: base_normal, color: FACE_COLOR, atlas_rect };
+    let base1_v2 = Vertex { position: base_v2_pos, light: YELLOW, uv: [1.0, 1.0], normal: base_normal, color: FACE_COLOR, atlas_rect };
+    let base2_v0 = Vertex { position: base_v0_pos, light: YELLOW, uv: [0.0, 0.0], normal: base_normal, color: FACE_COLOR, atlas_rect };
+    let base2_v2 = Vertex { position: base_v2_pos, light: YELLOW, uv: [1.0, 1.0], normal: base_normal, color: FACE_COLOR, atlas_rect };
+    let base2_v3 = Vertex { position: base_v3_pos, light: YELLOW, uv: [0.0, 1.0], normal: base_normal, color: FACE_COLOR, atlas_rect };
 
     vec![
         // Front face
@@ -177,10 +201,10 @@ fn get_entity_vertices(entity: &EcosimEntity, camera_pos: Point3<f32>) -> Vec<Ve
 
     let normal = calc_normal(base_left_pos, base_right_pos, top_left_pos);
 
-    let base_left = Vertex { position: base_left_pos, light: [0.0, 0.0, 0.0], uv: [uv_offset_x, uv_offset_y + uv_scale], normal };
-    let base_right = Vertex { position: base_right_pos, light: [0.0, 0.0, 0.0], uv: [uv_offset_x + uv_scale, uv_offset_y + uv_scale], normal };
-    let top_left = Vertex { position: top_left_pos, light: [0.0, 0.0, 0.0], uv: [uv_offset_x, uv_offset_y], normal };
-    let top_right = Vertex { position: top_right_pos, light: [0.0, 0.0, 0.0], uv: [uv_offset_x + uv_scale, uv_offset_y], normal };
+    let base_left = Vertex { position: base_left_pos, light: [0.0, 0.0, 0.0], uv: [uv_offset_x, uv_offset_y + uv_scale], normal, color: [1.0, 1.0, 1.0], atlas_rect: AtlasRect::FULL.to_array() };
+    let base_right = Vertex { position: base_right_pos, light: [0.0, 0.0, 0.0], uv: [uv_offset_x + uv_scale, uv_offset_y + uv_scale], normal, color: [1.0, 1.0, 1.0], atlas_rect: AtlasRect::FULL.to_array() };
+    let top_left = Vertex { position: top_left_pos, light: [0.0, 0.0, 0.0], uv: [uv_offset_x, uv_offset_y], normal, color: [1.0, 1.0, 1.0], atlas_rect: AtlasRect::FULL.to_array() };
+    let top_right = Vertex { position: top_right_pos, light: [0.0, 0.0, 0.0], uv: [uv_offset_x + uv_scale, uv_offset_y], normal, color: [1.0, 1.0, 1.0], atlas_rect: AtlasRect::FULL.to_array() };
 
     vec![
         base_left, top_left, top_right,
@@ -191,7 +215,7 @@ fn get_entity_vertices(entity: &EcosimEntity, camera_pos: Point3<f32>) -> Vec<Ve
 pub struct GameState {
     pub exit: bool,
     pub window_size: Vector2<u32>,
-    pub chunk: VoxelChunk,
+    pub world: VoxelWorld,
     pub camera: Camera,
     first_person_camera_controller: FirstPersonCameraController,
     orbit_camera_controller: OrbitCameraController,
@@ -201,17 +225,41 @@ pub struct GameState {
     pub player: PlayerActor,
     ecosim_tick_accumulator: f64,
     pub ecosim_entities: Vec<EcosimEntity>,
+    pub atlas: Arc<TextureAtlas>,
+    /// The voxel under the crosshair, if it's within reach.
+    target: Option<VoxelHit>,
+    /// The flower under the crosshair, if it's in front of `target`, as an index into
+    /// `ecosim_entities`.
+    targeted_flower: Option<usize>,
+    streamer: ChunkStreamer,
+    placed_voxel: VoxelType,
+    spawn_position: Point3<Fixed>,
+    // Chunks whose generated flowers have been added, so generating a chunk again after it's
+    // evicted doesn't add them twice.
+    flowers_spawned: HashSet<Vector3<i32>>,
 }
 
 impl GameState {
-    pub fn new() -> Self {
+    /// A world made from `seed`, the same one every time.
+    pub fn new(seed: u64) -> Self {
+        let terrain = Arc::new(NoiseTerrain::new(seed));
+        // In the middle of the voxel.
+        let spawn_position = point3(Fixed::new(0, 64), Fixed::new(terrain.spawn_height(0, 0), 0), Fixed::new(0, 64));
         let mut player = PlayerActor::new();
-        player.body.position = point3(Fixed::new(2, 0), Fixed::new(3, 0), Fixed::new(2, 0));
+        player.body.position = spawn_position;
         player.body.collision_size = vec3(Fixed::new(0, 128), Fixed::new(2, 0), Fixed::new(0, 128));
+        player.body.step_height = Fixed::new(1, 0);
+        player.body.walk_acceleration = Fixed::new(0, 4);
+        player.body.air_control = Fixed::new(0, 64);
+        let atlas = Arc::new(TextureAtlas::new());
+        // Each seed is a different world, so it gets its own saves.
+        let save_dir = PathBuf::from(SAVE_DIR).join(seed.to_string());
+        let streaming_config = StreamingConfig { save_dir: Some(save_dir), ..Default::default() };
+        let streamer = ChunkStreamer::new(streaming_config, terrain, atlas.clone());
         GameState {
             exit: false,
             window_size: vec2(0, 0),
-            chunk: VoxelChunk::new(),
+            world: VoxelWorld::new(),
             camera: Camera::new(point3(-2.0, 0.0, 2.0), point3(0.25, 0.25, 0.25), 0.0),
             first_person_camera_controller: FirstPersonCameraController {
                 pitch: 0.0,
@@ -224,10 +272,25 @@ impl GameState {
             },
             is_camera_first_person: true,
             physics_tick_accumulator: 0.0,
-            physics_config: PhysicsConfig { gravity: vec3(Fixed::ZERO, -Fixed::new(0, 3), Fixed::ZERO) },
+            physics_config: PhysicsConfig {
+                gravity: vec3(Fixed::ZERO, -Fixed::new(0, 3), Fixed::ZERO),
+                ground_friction: Fixed::new(0, 64),
+                air_friction: Fixed::new(0, 5),
+                max_speed: vec3(Fixed::new(1, 0), Fixed::new(1, 0), Fixed::new(1, 0)),
+                max_substeps: 4,
+                overflow_behavior: OverflowBehavior::Saturate,
+                boundary_policy: BoundaryPolicy::OpenVoid,
+            },
             player,
             ecosim_tick_accumulator: 0.0,
             ecosim_entities: vec![],
+            atlas,
+            target: None,
+            targeted_flower: None,
+            streamer,
+            placed_voxel: DIRT,
+            spawn_position,
+            flowers_spawned: HashSet::new(),
         }
     }
 
@@ -236,47 +299,14 @@ impl GameState {
         self.camera.aspect_ratio = window_size.x as f32 / window_size.y as f32;
     }
 
-    pub fn generate_voxels(&mut self) {
-        for i in 2..CHUNK_SIZE.x {
-            for k in 2..CHUNK_SIZE.z {
-                for j in 0..3 {
-                    self.chunk.set_voxel(vec3(i, j, k), 1);
-                }
-            }
-        }
-        self.chunk.set_voxel(vec3(4, 4, 4), 1);
-
-        self.chunk.set_voxel(vec3(3, 3, 2), 1);
-        self.chunk.set_voxel(vec3(4, 3, 2), 1);
-        self.chunk.set_voxel(vec3(4, 4, 2), 1);
-
-        self.chunk.set_voxel(vec3(12, 3, 12), 1);
-        self.chunk.set_voxel(vec3(13, 3, 12), 1);
-        self.chunk.set_voxel(vec3(14, 3, 12), 1);
-        self.chunk.set_voxel(vec3(12, 3, 13), 1);
-        self.chunk.set_voxel(vec3(13, 3, 13), 1);
-        self.chunk.set_voxel(vec3(14, 3, 13), 1);
-        self.chunk.set_voxel(vec3(12, 3, 14), 1);
-        self.chunk.set_voxel(vec3(13, 3, 14), 1);
-        self.chunk.set_voxel(vec3(14, 3, 14), 1);
-        self.chunk.set_voxel(vec3(13, 4, 13), 1);
-
-        self.ecosim_entities.push(EcosimEntity::new(vec3(8, 3, 3)));
-        self.ecosim_entities.push(EcosimEntity::new(vec3(9, 3, 4)));
-        self.ecosim_entities.push(EcosimEntity::new(vec3(12, 3, 8)));
-        self.ecosim_entities.push(EcosimEntity::new(vec3(12, 3, 8)));
-        self.ecosim_entities.push(EcosimEntity::new(vec3(24, 3, 8)));
-        self.ecosim_entities.push(EcosimEntity::new(vec3(14, 3, 30)));
-        self.ecosim_entities.push(EcosimEntity::new(vec3(6, 3, 2)));
-        for e in self.ecosim_entities.iter_mut() {
-            e.randomize_genome();
-        }
-    }
-
     pub fn on_key_pressed(&mut self, key_code: KeyCode) {
         match key_code {
             KeyCode::KeyQ => self.exit = true,
             KeyCode::KeyC => self.is_camera_first_person = !self.is_camera_first_person,
+            KeyCode::Digit1 => self.placed_voxel = PLACEABLE_VOXELS[0],
+            KeyCode::Digit2 => self.placed_voxel = PLACEABLE_VOXELS[1],
+            KeyCode::Digit3 => self.placed_voxel = PLACEABLE_VOXELS[2],
+            KeyCode::Digit4 => self.placed_voxel = PLACEABLE_VOXELS[3],
             KeyCode::Space => if self.player.body.is_on_ground {
                 self.player.body.velocity.y = Fixed::new(0, 48);
             },
@@ -284,50 +314,113 @@ impl GameState {
         };
     }
 
-    fn calculate_light(&mut self) {
-        for i in 0..CHUNK_SIZE.x {
-            for j in 0..CHUNK_SIZE.y {
-                for k in 0..CHUNK_SIZE.z {
-                    self.chunk.set_voxel_light(vec3(i, j, k), [i as f32 / CHUNK_SIZE.x as f32, k as f32 / CHUNK_SIZE.z as f32, 1.0]);
-                }
-            }
+    pub fn on_mouse_pressed(&mut self, button: MouseButton) {
+        let Some(target) = self.target else {
+            return;
+        };
+        match button {
+            MouseButton::Left => self.break_voxel(target.voxel),
+            MouseButton::Right if target.normal != vec3(0, 0, 0) => self.place_voxel(target.voxel + target.normal),
+            _ => (),
+        };
+    }
+
+    fn break_voxel(&mut self, coord: Vector3<i32>) {
+        self.world.set_voxel(coord, AIR);
+        relight_voxel(&mut self.world, coord);
+        // Flowers standing on the block lose their ground.
+        kill_entities_at(&mut self.ecosim_entities, coord + vec3(0, 1, 0));
+        self.target = None;
+    }
+
+    fn place_voxel(&mut self, coord: Vector3<i32>) {
+        if !self.world.is_loaded(coord) || self.player.body.overlaps_voxel(coord) {
+            return;
         }
+        self.world.set_voxel(coord, self.placed_voxel);
+        relight_voxel(&mut self.world, coord);
+        kill_entities_at(&mut self.ecosim_entities, coord);
+        self.target = None;
+    }
+
+    // The nearest flower along the camera's forward ray, in reach and not behind the targeted voxel.
+    fn pick_flower(&self) -> Option<usize> {
+        let max_distance = self.target.map_or(FixedFine::new(PLAYER_REACH, 0), |hit| hit.distance);
+        let (eye, forward) = (self.camera.position / VOXEL_SCALE, self.first_person_camera_controller.get_forward());
+        // Too far from the world origin for `FixedFine` means nothing can be picked.
+        let fine = FixedFine::checked_from_f32;
+        let origin = point3(fine(eye.x)?, fine(eye.y)?, fine(eye.z)?);
+        let direction = vec3(fine(forward.x)?, fine(forward.y)?, fine(forward.z)?);
+        raycast_entities(&self.ecosim_entities, origin, direction, Some(max_distance)).map(|hit| hit.index)
+    }
+
+    /// A line about the flower under the crosshair, for the HUD.
+    pub fn get_targeted_flower_description(&self) -> Option<String> {
+        let flower = self.ecosim_entities.get(self.targeted_flower?)?;
+        let state = if flower.dead_ticks.is_some() { "dead" } else { "alive" };
+        Some(format!("flower: genome {:08x}, age {} ticks, stress {}, {state}", flower.genome, flower.age_ticks, flower.stress))
+    }
+
+    /// Writes changes to modified chunks to disk.
+    pub fn save_world(&mut self) {
+        self.streamer.save_all(&mut self.world);
     }
 
     pub fn update(&mut self, dt: f64, input_state: &InputState) {
+        let player_voxel = self.player.body.position.to_vec().map(Fixed::floor_i32);
+        for loaded in self.streamer.update(&mut self.world, player_voxel) {
+            light_new_chunk(&mut self.world, loaded.chunk_coord);
+            if self.flowers_spawned.insert(loaded.chunk_coord) {
+                let flowers = loaded.flowers.iter().map(|flower| EcosimEntity::with_genome(flower.position, flower.genome));
+                self.ecosim_entities.extend(flowers);
+            }
+        }
+
+        // Hold the player in place until the ground around them has loaded.
+        let player_chunk = voxel_to_chunk(player_voxel);
+        let is_ground_loaded = [player_chunk, player_chunk - vec3(0, 1, 0)].iter().all(|&c| self.world.get_chunk(c).is_some());
         self.physics_tick_accumulator += dt;
+        if !is_ground_loaded {
+            self.physics_tick_accumulator = 0.0;
+        }
         while self.physics_tick_accumulator > PHYSICS_SECONDS_PER_TICK {
-            const PLAYER_SPEED: f32 = 0.05;
-            let forward = self.first_person_camera_controller.get_forward();
-            let forward_xz = vec3(forward.x, 0.0, forward.z).normalize();
-            let forward_velocity = Fixed::vector3_from_f32(forward_xz * PLAYER_SPEED);
-            let right = forward_xz.cross(cgmath::Vector3::unit_y()).normalize();
-            let right_velocity = Fixed::vector3_from_f32(right * PLAYER_SPEED);
-            self.player.body.velocity.x = Fixed::ZERO;
-            self.player.body.velocity.z = Fixed::ZERO;
+            let player_speed = Fixed::new(0, 13);
+            // Movement is computed in fixed point so the simulation doesn't depend on platform floats.
+            let yaw = Fixed::from_f32(self.first_person_camera_controller.yaw);
+            let forward_velocity = vec3(yaw.cos(), Fixed::ZERO, yaw.sin()) * player_speed;
+            let right_velocity = vec3(-forward_velocity.z, Fixed::ZERO, forward_velocity.x);
+            self.player.body.walk_velocity = Fixed::ZERO_VECTOR;
             if input_state.is_key_pressed(KeyCode::KeyW) {
-                self.player.body.velocity.x += forward_velocity.x;
-                self.player.body.velocity.z += forward_velocity.z;
+                self.player.body.walk_velocity += forward_velocity;
             }
             if input_state.is_key_pressed(KeyCode::KeyS) {
-                self.player.body.velocity.x -= forward_velocity.x;
-                self.player.body.velocity.z -= forward_velocity.z;
+                self.player.body.walk_velocity -= forward_velocity;
             }
             if input_state.is_key_pressed(KeyCode::KeyD) {
-                self.player.body.velocity.x += right_velocity.x;
-                self.player.body.velocity.z += right_velocity.z;
+                self.player.body.walk_velocity += right_velocity;
             }
             if input_state.is_key_pressed(KeyCode::KeyA) {
-                self.player.body.velocity.x -= right_velocity.x;
-                self.player.body.velocity.z -= right_velocity.z;
+                self.player.body.walk_velocity -= right_velocity;
+            }
+            let events = physics_tick(&self.physics_config, std::slice::from_mut(&mut self.player.body), &self.world);
+            // Chunks keep loading under a falling player, so they'd never leave the world.
+            let mut respawn = self.player.body.position.y < Fixed::new(KILL_DEPTH, 0);
+            for event in events {
+                match event {
+                    PhysicsEvent::OutOfWorld { .. } => respawn = true,
+                    PhysicsEvent::Contact { .. } | PhysicsEvent::BudgetExceeded { .. } => (),
+                }
+            }
+            if respawn {
+                self.player.body.position = self.spawn_position;
+                self.player.body.velocity = Fixed::ZERO_VECTOR;
             }
-            physics_tick(&self.physics_config, std::slice::from_mut(&mut self.player.body), &self.chunk);
             self.physics_tick_accumulator -= PHYSICS_SECONDS_PER_TICK;
         }
 
         self.ecosim_tick_accumulator += dt;
         while self.ecosim_tick_accumulator > ECOSIM_SECONDS_PER_TICK {
-            ecosim_tick(&mut self.ecosim_entities, &self.chunk);
+            ecosim_tick(&mut self.ecosim_entities, &self.world);
             self.ecosim_tick_accumulator -= ECOSIM_SECONDS_PER_TICK;
         }
 
@@ -350,12 +443,20 @@ impl GameState {
             if input_state.is_key_pressed(KeyCode::ArrowLeft) {
                 self.first_person_camera_controller.yaw -= 0.01;
             }
+            // Keep yaw small so converting it to fixed point for movement can't overflow.
+            self.first_person_camera_controller.yaw %= std::f32::consts::TAU;
 
             let player_center_base = self.player.get_center_base_f32();
             let eye_height = self.player.body.collision_size.y.to_f32() * VOXEL_SCALE * 0.95;
             self.camera.position = player_center_base + vec3(0.0, eye_height, 0.0);
             self.camera.target = self.first_person_camera_controller.get_camera_target(&self.camera.position);
+
+            let options = RaycastOptions { max_distance: Some(FixedFine::new(PLAYER_REACH, 0)), ..Default::default() };
+            self.target = raycast_voxels_f32(&self.world, self.camera.position / VOXEL_SCALE, self.first_person_camera_controller.get_forward(), &options);
+            self.targeted_flower = self.pick_flower();
         } else {
+            self.target = None;
+            self.targeted_flower = None;
             if input_state.is_key_pressed(KeyCode::ArrowUp) {
                 self.orbit_camera_controller.zoom -= 0.01;
             }
@@ -381,17 +482,50 @@ impl GameState {
             self.camera.target = self.player.get_center_f32();
             self.camera.position = self.orbit_camera_controller.get_camera_position(&self.camera.target);
         }
+    }
 
-        self.calculate_light();
+    /// Each meshed chunk's coordinate, mesh, and mesh generation, which changes whenever the mesh
+    /// does. Chunks are meshed in the background, so a chunk can show its old mesh for a few
+    /// frames after it changes.
+    pub fn get_voxel_meshes(&self) -> impl Iterator<Item = (Vector3<i32>, &ChunkMesh, u64)> {
+        self.world.chunks().filter_map(|(coord, chunk)| {
+            let (mesh, generation) = chunk.get_mesh();
+            (generation > 0).then_some((coord, mesh, generation))
+        })
     }
 
-    pub fn get_voxel_vertices(&mut self) -> Vec<Vertex> {
-        let mut vertices = self.chunk.get_vertices();
+    pub fn get_player_vertices(&self) -> Vec<Vertex> {
         // Center the player model on the hitbox base
-        vertices.append(&mut create_pyramid_mesh(
-                self.player.get_center_base_f32(),
-                self.player.body.collision_size.x.to_f32() * VOXEL_SCALE,
-                self.player.body.collision_size.y.to_f32() * VOXEL_SCALE));
+        create_pyramid_mesh(
+            self.player.get_center_base_f32(),
+            self.player.body.collision_size.x.to_f32() * VOXEL_SCALE,
+            self.player.body.collision_size.y.to_f32() * VOXEL_SCALE,
+            self.atlas.get("noise_128").to_array())
+    }
+
+    pub fn get_target_outline_vertices(&self) -> Vec<LineVertex> {
+        const COLOR: [f32; 3] = [0.0, 0.0, 0.0];
+        // Slightly larger than the voxel so the lines aren't hidden by its faces.
+        const GROW: f32 = 0.002;
+        let Some(target) = self.target else {
+            return vec![];
+        };
+        let min = target.voxel.map(|c| c as f32 * VOXEL_SCALE - GROW);
+        let max = target.voxel.map(|c| (c + 1) as f32 * VOXEL_SCALE + GROW);
+        let corner = |i: usize| {
+            let pick = |bit: usize, axis: usize| if i & bit == 0 { min[axis] } else { max[axis] };
+            LineVertex { position: [pick(1, 0), pick(2, 1), pick(4, 2)], color: COLOR }
+        };
+        let mut vertices = vec![];
+        // Corners are numbered by bits for x, y and z. Each edge joins two corners one bit apart.
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    vertices.push(corner(i));
+                    vertices.push(corner(i | bit));
+                }
+            }
+        }
         vertices
     }
 
@@ -413,3 +547,89 @@ impl GameState {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::voxel::{CHUNK_SIZE, VoxelChunk};
+
+    fn hit(voxel: Vector3<i32>, normal: Vector3<i32>) -> Option<VoxelHit> {
+        Some(VoxelHit { voxel, normal, distance: FixedFine::ZERO })
+    }
+
+    // A flat chunk with the player on it, loaded straight away rather than streamed in.
+    fn spawn_chunk_game_state() -> GameState {
+        let mut game_state = GameState::new(0);
+        game_state.world.insert_chunk(vec3(0, 0, 0), VoxelChunk::new());
+        for i in 0..CHUNK_SIZE.x as i32 {
+            for k in 0..CHUNK_SIZE.z as i32 {
+                game_state.world.set_voxel(vec3(i, 2, k), DIRT);
+            }
+        }
+        game_state.player.body.position = point3(Fixed::new(2, 0), Fixed::new(3, 0), Fixed::new(2, 0));
+        game_state
+    }
+
+    #[test]
+    fn break_voxel() {
+        let mut game_state = spawn_chunk_game_state();
+        game_state.ecosim_entities = vec![ EcosimEntity::new(vec3(8, 3, 3)), EcosimEntity::new(vec3(9, 3, 3)) ];
+
+        game_state.target = hit(vec3(8, 2, 3), vec3(0, 1, 0));
+        game_state.on_mouse_pressed(MouseButton::Left);
+        assert_eq!(Some(AIR), game_state.world.get_voxel(vec3(8, 2, 3)));
+        assert_eq!(Some(0), game_state.ecosim_entities[0].dead_ticks);
+        assert_eq!(None, game_state.ecosim_entities[1].dead_ticks);
+        assert_eq!(None, game_state.target);
+    }
+
+    #[test]
+    fn place_voxel() {
+        let mut game_state = spawn_chunk_game_state();
+        game_state.ecosim_entities = vec![ EcosimEntity::new(vec3(8, 3, 3)) ];
+
+        game_state.target = hit(vec3(8, 2, 3), vec3(0, 1, 0));
+        game_state.on_mouse_pressed(MouseButton::Right);
+        assert_eq!(Some(DIRT), game_state.world.get_voxel(vec3(8, 3, 3)));
+        assert_eq!(Some(0), game_state.ecosim_entities[0].dead_ticks);
+
+        // Not inside the player, nor in a chunk that isn't loaded.
+        let player = game_state.player.body.position.to_vec().map(Fixed::floor_i32);
+        game_state.target = hit(player - vec3(0, 1, 0), vec3(0, 1, 0));
+        game_state.on_mouse_pressed(MouseButton::Right);
+        assert_eq!(Some(AIR), game_state.world.get_voxel(player));
+        game_state.target = hit(vec3(8, 31, 3), vec3(0, 1, 0));
+        game_state.on_mouse_pressed(MouseButton::Right);
+        assert_eq!(None, game_state.world.get_voxel(vec3(8, 32, 3)));
+    }
+
+    #[test]
+    fn pick_flower() {
+        let mut game_state = spawn_chunk_game_state();
+        game_state.ecosim_entities = vec![
+            EcosimEntity::with_genome(point3(Fixed::new(6, 128), Fixed::new(3, 0), Fixed::new(2, 128)), 0xabcd),
+            EcosimEntity::with_genome(point3(Fixed::new(4, 128), Fixed::new(3, 0), Fixed::new(9, 128)), 0),
+        ];
+        // Looking along +x at the first flower's middle.
+        game_state.camera.position = point3(2.0, 3.5, 2.5) * VOXEL_SCALE;
+        assert_eq!(Some(0), game_state.pick_flower());
+        game_state.targeted_flower = game_state.pick_flower();
+        assert!(game_state.get_targeted_flower_description().unwrap().contains("0000abcd"));
+
+        // A voxel in front of it hides it.
+        game_state.target = Some(VoxelHit { voxel: vec3(4, 3, 2), normal: vec3(-1, 0, 0), distance: FixedFine::new(2, 0) });
+        assert_eq!(None, game_state.pick_flower());
+        game_state.target = None;
+        game_state.first_person_camera_controller.yaw = std::f32::consts::PI;
+        assert_eq!(None, game_state.pick_flower());
+    }
+
+    #[test]
+    fn target_outline() {
+        let mut game_state = GameState::new(0);
+        assert!(game_state.get_target_outline_vertices().is_empty());
+        game_state.target = hit(vec3(1, 2, 3), vec3(0, 1, 0));
+        assert_eq!(24, game_state.get_target_outline_vertices().len());
+    }
+}
@@ -0,0 +1,427 @@
+use std::collections::VecDeque;
+
+use cgmath::{Vector3, vec3};
+
+use crate::material::material;
+use crate::voxel::{CHUNK_SIZE, FACE_DIRECTIONS, VoxelChunk, VoxelType};
+use crate::voxel_world::{VoxelWorld, chunk_origin};
+
+/// The brightest a light level gets.
+pub const MAX_LIGHT: u8 = 15;
+
+/// Light levels in a voxel, from 0 to `MAX_LIGHT`: sky light, then red, green and blue block light.
+pub type VoxelLight = [u8; 4];
+
+const SKY: usize = 0;
+const CHANNELS: usize = 4;
+
+/// What faces against chunks that aren't loaded are lit with, since there could be anything there.
+pub const UNLOADED_LIGHT: VoxelLight = [MAX_LIGHT, 0, 0, 0];
+
+/// The colour a face lit with `light` is multiplied by. Sky light is white. Each level is a fifth
+/// dimmer than the one above, down to a little ambient light.
+pub fn light_color(light: VoxelLight) -> [f32; 3] {
+    const AMBIENT: f32 = 0.04;
+    let brightness = |level: u8| AMBIENT + (1.0 - AMBIENT) * 0.8f32.powi((MAX_LIGHT - level) as i32);
+    [1, 2, 3].map(|channel| brightness(light[SKY].max(light[channel])))
+}
+
+fn emission(voxel: VoxelType) -> VoxelLight {
+    let [r, g, b] = material(voxel).light_emission;
+    [0, r, g, b]
+}
+
+// How bright light of `level` is in the next voxel over in `direction`. Full sky light goes
+// straight down without getting dimmer, so everything under open sky is fully lit.
+fn spread_level(channel: usize, level: u8, direction: Vector3<i32>) -> u8 {
+    if channel == SKY && level == MAX_LIGHT && direction == vec3(0, -1, 0) {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+// Somewhere light spreads through: the whole world, or a chunk on its own.
+trait LightVolume {
+    // `None` where nothing's loaded, which light doesn't spread into.
+    fn voxel(&self, coord: Vector3<i32>) -> Option<VoxelType>;
+    fn light(&self, coord: Vector3<i32>) -> VoxelLight;
+    fn set_light(&mut self, coord: Vector3<i32>, light: VoxelLight);
+
+    fn set_channel(&mut self, coord: Vector3<i32>, channel: usize, level: u8) {
+        let mut light = self.light(coord);
+        light[channel] = level;
+        self.set_light(coord, light);
+    }
+}
+
+impl LightVolume for VoxelWorld {
+    fn voxel(&self, coord: Vector3<i32>) -> Option<VoxelType> {
+        self.get_voxel(coord)
+    }
+
+    fn light(&self, coord: Vector3<i32>) -> VoxelLight {
+        self.get_light(coord).unwrap_or_default()
+    }
+
+    fn set_light(&mut self, coord: Vector3<i32>, light: VoxelLight) {
+        VoxelWorld::set_light(self, coord, light);
+    }
+}
+
+impl LightVolume for VoxelChunk {
+    fn voxel(&self, coord: Vector3<i32>) -> Option<VoxelType> {
+        (!self.is_i32_out_of_bounds(coord)).then(|| self.get_voxel_i32(coord))
+    }
+
+    fn light(&self, coord: Vector3<i32>) -> VoxelLight {
+        self.get_voxel_light(coord.map(|c| c as usize))
+    }
+
+    fn set_light(&mut self, coord: Vector3<i32>, light: VoxelLight) {
+        self.set_voxel_light(coord.map(|c| c as usize), light);
+    }
+}
+
+// Breadth-first work for each channel separately. Removing light comes first, and finds the voxels
+// that still have light to spread back into the gap.
+#[derive(Default)]
+struct LightQueues {
+    // Voxels whose light in a channel spreads to their neighbours.
+    spread: VecDeque<(Vector3<i32>, usize)>,
+    // Voxels whose light in a channel, at the level it was, is taken away from what it lit.
+    remove: VecDeque<(Vector3<i32>, usize, u8)>,
+}
+
+impl LightQueues {
+    fn remove(&mut self, volume: &mut impl LightVolume, coord: Vector3<i32>, channel: usize) {
+        let level = volume.light(coord)[channel];
+        if level > 0 {
+            volume.set_channel(coord, channel, 0);
+            self.remove.push_back((coord, channel, level));
+        }
+    }
+
+    fn run(&mut self, volume: &mut impl LightVolume) {
+        while let Some((coord, channel, level)) = self.remove.pop_front() {
+            for direction in FACE_DIRECTIONS {
+                let neighbor = coord + direction;
+                let Some(voxel) = volume.voxel(neighbor) else {
+                    continue;
+                };
+                let neighbor_level = volume.light(neighbor)[channel];
+                if neighbor_level == 0 {
+                    continue;
+                }
+                if neighbor_level <= spread_level(channel, level, direction) {
+                    // Could have been lit from here, so it goes too.
+                    volume.set_channel(neighbor, channel, 0);
+                    self.remove.push_back((neighbor, channel, neighbor_level));
+                    let emitted = emission(voxel)[channel];
+                    if emitted > 0 {
+                        volume.set_channel(neighbor, channel, emitted);
+                        self.spread.push_back((neighbor, channel));
+                    }
+                } else {
+                    // Lit from somewhere else, so it lights the gap back up.
+                    self.spread.push_back((neighbor, channel));
+                }
+            }
+        }
+
+        while let Some((coord, channel)) = self.spread.pop_front() {
+            let level = volume.light(coord)[channel];
+            for direction in FACE_DIRECTIONS {
+                let neighbor = coord + direction;
+                let new_level = spread_level(channel, level, direction);
+                if new_level == 0 || volume.voxel(neighbor).is_none_or(|voxel| material(voxel).is_opaque) {
+                    continue;
+                }
+                if volume.light(neighbor)[channel] < new_level {
+                    volume.set_channel(neighbor, channel, new_level);
+                    self.spread.push_back((neighbor, channel));
+                }
+            }
+        }
+    }
+
+    // Lights an emissive voxel with its own light.
+    fn emit(&mut self, volume: &mut impl LightVolume, coord: Vector3<i32>, voxel: VoxelType) {
+        for (channel, &level) in emission(voxel).iter().enumerate() {
+            if level > volume.light(coord)[channel] {
+                volume.set_channel(coord, channel, level);
+                self.spread.push_back((coord, channel));
+            }
+        }
+    }
+
+    fn spread_all_channels(&mut self, volume: &impl LightVolume, coord: Vector3<i32>) {
+        for (channel, &level) in volume.light(coord).iter().enumerate() {
+            if level > 0 {
+                self.spread.push_back((coord, channel));
+            }
+        }
+    }
+}
+
+/// Lights a chunk on its own, as if it were under open sky with nothing around it. Once it's in
+/// the world, `light_new_chunk` joins its light up with its neighbours'.
+pub fn light_chunk(chunk: &mut VoxelChunk) {
+    chunk.clear_light();
+    let mut queues = LightQueues::default();
+    let size = CHUNK_SIZE.map(|s| s as i32);
+    for i in 0..size.x {
+        for k in 0..size.z {
+            for j in (0..size.y).rev() {
+                let coord = vec3(i, j, k);
+                if material(chunk.get_voxel_i32(coord)).is_opaque {
+                    break;
+                }
+                chunk.set_channel(coord, SKY, MAX_LIGHT);
+            }
+        }
+    }
+    // Sky light only has to spread sideways out of the columns, into whatever's under an overhang.
+    // Open air is already lit, so a chunk of it costs nothing more.
+    let sides = [vec3(1, 0, 0), vec3(-1, 0, 0), vec3(0, 0, 1), vec3(0, 0, -1)];
+    for i in 0..size.x {
+        for j in 0..size.y {
+            for k in 0..size.z {
+                let coord = vec3(i, j, k);
+                queues.emit(chunk, coord, chunk.get_voxel_i32(coord));
+                let is_lit_column = chunk.light(coord)[SKY] == MAX_LIGHT;
+                let overhang = |side: Vector3<i32>| chunk.voxel(coord + side).is_some_and(|voxel| !material(voxel).is_opaque && chunk.light(coord + side)[SKY] < MAX_LIGHT);
+                if is_lit_column && sides.into_iter().any(overhang) {
+                    queues.spread.push_back((coord, SKY));
+                }
+            }
+        }
+    }
+    queues.run(chunk);
+}
+
+/// Spreads light between a chunk just added to the world, lit with `light_chunk`, and the chunks
+/// around it.
+pub fn light_new_chunk(world: &mut VoxelWorld, chunk_coord: Vector3<i32>) {
+    let origin = chunk_origin(chunk_coord);
+    let size = CHUNK_SIZE.map(|s| s as i32);
+    let mut queues = LightQueues::default();
+
+    // Chunks are lit as if there's open sky above them, so where a chunk is under another one,
+    // sky light that the one above doesn't let through comes out of it. The top of this chunk goes
+    // first, so the bottom is right before it's checked against the chunk below.
+    for seam_y in [origin.y + size.y, origin.y] {
+        for i in 0..size.x {
+            for k in 0..size.z {
+                let lower = vec3(origin.x + i, seam_y - 1, origin.z + k);
+                let upper = lower + vec3(0, 1, 0);
+                let (Some(lower_light), Some(upper_light)) = (world.get_light(lower), world.get_light(upper)) else {
+                    continue;
+                };
+                if lower_light[SKY] == MAX_LIGHT && upper_light[SKY] != MAX_LIGHT {
+                    queues.remove(world, lower, SKY);
+                }
+            }
+        }
+        queues.run(world);
+    }
+
+    // Light on either side of each border spreads across it, where it's brighter than what's
+    // already on the other side.
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for (inside, direction) in [(0, -1), (size[axis] - 1, 1)] {
+            let mut step = vec3(0, 0, 0);
+            step[axis] = direction;
+            for i in 0..size[u] {
+                for j in 0..size[v] {
+                    let mut offset = vec3(0, 0, 0);
+                    (offset[u], offset[v], offset[axis]) = (i, j, inside);
+                    let (near, far) = (origin + offset, origin + offset + step);
+                    let (Some(near_light), Some(far_light)) = (world.get_light(near), world.get_light(far)) else {
+                        continue;
+                    };
+                    for channel in 0..CHANNELS {
+                        if spread_level(channel, near_light[channel], step) > far_light[channel] {
+                            queues.spread.push_back((near, channel));
+                        }
+                        if spread_level(channel, far_light[channel], -step) > near_light[channel] {
+                            queues.spread.push_back((far, channel));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    queues.run(world);
+}
+
+/// Updates the light around a voxel that was just changed, only as far as the change reaches.
+pub fn relight_voxel(world: &mut VoxelWorld, coord: Vector3<i32>) {
+    let Some(voxel) = world.get_voxel(coord) else {
+        return;
+    };
+    let mut queues = LightQueues::default();
+    for channel in 0..CHANNELS {
+        queues.remove(world, coord, channel);
+    }
+    queues.run(world);
+
+    queues.emit(world, coord, voxel);
+    if !material(voxel).is_opaque {
+        // Chunks are lit as if there's open sky above them.
+        if !world.is_loaded(coord + vec3(0, 1, 0)) {
+            world.set_channel(coord, SKY, MAX_LIGHT);
+            queues.spread.push_back((coord, SKY));
+        }
+        for direction in FACE_DIRECTIONS {
+            if world.is_loaded(coord + direction) {
+                queues.spread_all_channels(world, coord + direction);
+            }
+        }
+    }
+    queues.run(world);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::material::{AIR, DIRT, LAMP, STONE, WATER};
+
+    fn sky(world: &VoxelWorld, coord: Vector3<i32>) -> u8 {
+        world.get_light(coord).unwrap()[SKY]
+    }
+
+    fn world_with(chunks: &[(Vector3<i32>, VoxelChunk)]) -> VoxelWorld {
+        let mut world = VoxelWorld::new();
+        for (chunk_coord, chunk) in chunks {
+            let mut chunk = chunk.snapshot();
+            light_chunk(&mut chunk);
+            world.insert_chunk(*chunk_coord, chunk);
+            light_new_chunk(&mut world, *chunk_coord);
+        }
+        world
+    }
+
+    fn all_light(world: &VoxelWorld) -> Vec<(Vector3<i32>, VoxelLight)> {
+        let mut lights = vec![];
+        let mut chunk_coords: Vec<_> = world.chunks().map(|(chunk_coord, _)| chunk_coord).collect();
+        chunk_coords.sort_by_key(|c| (c.x, c.y, c.z));
+        for chunk_coord in chunk_coords {
+            let origin = chunk_origin(chunk_coord);
+            for i in 0..CHUNK_SIZE.x as i32 {
+                for j in 0..CHUNK_SIZE.y as i32 {
+                    for k in 0..CHUNK_SIZE.z as i32 {
+                        let coord = origin + vec3(i, j, k);
+                        lights.push((coord, world.get_light(coord).unwrap()));
+                    }
+                }
+            }
+        }
+        lights
+    }
+
+    #[test]
+    fn sky_columns() {
+        let mut chunk = VoxelChunk::new();
+        chunk.set_voxel(vec3(5, 20, 5), STONE);
+        chunk.set_voxel(vec3(9, 20, 9), WATER);
+        let world = world_with(&[(vec3(0, 0, 0), chunk)]);
+        assert_eq!(MAX_LIGHT, sky(&world, vec3(5, 21, 5)));
+        assert_eq!(0, sky(&world, vec3(5, 20, 5)));
+        // Under the stone it's lit from the side.
+        assert_eq!(MAX_LIGHT - 1, sky(&world, vec3(5, 19, 5)));
+        assert_eq!(MAX_LIGHT - 1, sky(&world, vec3(5, 0, 5)));
+        assert_eq!(MAX_LIGHT, sky(&world, vec3(9, 0, 9)));
+    }
+
+    #[test]
+    fn lamps_light_in_color() {
+        // A closed room, so there's no sky light.
+        let mut chunk = VoxelChunk::new();
+        for i in 0..CHUNK_SIZE.x {
+            for k in 0..CHUNK_SIZE.z {
+                chunk.set_voxel(vec3(i, 31, k), STONE);
+            }
+        }
+        let mut world = world_with(&[(vec3(0, 0, 0), chunk)]);
+        assert_eq!(Some([0; 4]), world.get_light(vec3(10, 10, 10)));
+
+        world.set_voxel(vec3(10, 10, 10), LAMP);
+        relight_voxel(&mut world, vec3(10, 10, 10));
+        assert_eq!(Some([0, 15, 12, 7]), world.get_light(vec3(10, 10, 10)));
+        assert_eq!(Some([0, 14, 11, 6]), world.get_light(vec3(11, 10, 10)));
+        assert_eq!(Some([0, 12, 9, 4]), world.get_light(vec3(11, 11, 9)));
+        assert_eq!(Some([0, 5, 2, 0]), world.get_light(vec3(10, 20, 10)));
+
+        // A wall between halves the light behind it.
+        for j in 0..31 {
+            for k in 0..CHUNK_SIZE.z as i32 {
+                world.set_voxel(vec3(12, j, k), STONE);
+                relight_voxel(&mut world, vec3(12, j, k));
+            }
+        }
+        assert_eq!(Some([0; 4]), world.get_light(vec3(13, 10, 10)));
+
+        world.set_voxel(vec3(10, 10, 10), AIR);
+        relight_voxel(&mut world, vec3(10, 10, 10));
+        assert_eq!(Some([0; 4]), world.get_light(vec3(10, 10, 10)));
+        assert_eq!(Some([0; 4]), world.get_light(vec3(11, 10, 10)));
+    }
+
+    #[test]
+    fn edits_match_lighting_from_scratch() {
+        let kinds = [AIR, AIR, DIRT, STONE, WATER, LAMP];
+        let mut chunk = VoxelChunk::new();
+        let mut state = 777u32;
+        let mut next = || {
+            state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+            state >> 8
+        };
+        for i in 0..CHUNK_SIZE.x {
+            for j in 0..12 {
+                for k in 0..CHUNK_SIZE.z {
+                    if next() % 3 == 0 {
+                        chunk.set_voxel(vec3(i, j, k), kinds[next() as usize % kinds.len()]);
+                    }
+                }
+            }
+        }
+        let mut world = world_with(&[(vec3(0, 0, 0), chunk)]);
+        for _ in 0..200 {
+            let coord = vec3(next() % 32, next() % 16, next() % 32).map(|c| c as i32);
+            world.set_voxel(coord, kinds[next() as usize % kinds.len()]);
+            relight_voxel(&mut world, coord);
+        }
+        let from_scratch = world_with(&[(vec3(0, 0, 0), world.get_chunk(vec3(0, 0, 0)).unwrap().snapshot())]);
+        assert!(all_light(&world) == all_light(&from_scratch));
+    }
+
+    #[test]
+    fn chunks_light_each_other() {
+        // A roof with a hole in it over one chunk, and a lamp in the chunk beside it.
+        let mut roof = VoxelChunk::new();
+        for i in 0..CHUNK_SIZE.x {
+            for k in 0..CHUNK_SIZE.z {
+                if (i, k) != (3, 3) {
+                    roof.set_voxel(vec3(i, 0, k), STONE);
+                }
+            }
+        }
+        let mut beside = VoxelChunk::new();
+        beside.set_voxel(vec3(20, 5, 0), LAMP);
+        let chunks = [(vec3(0, 0, 0), VoxelChunk::new()), (vec3(0, 1, 0), roof), (vec3(0, 0, 1), beside)];
+
+        let world = world_with(&chunks);
+        assert_eq!(MAX_LIGHT, sky(&world, vec3(3, 0, 3)));
+        assert_eq!(MAX_LIGHT - 1, sky(&world, vec3(4, 31, 3)));
+        assert_eq!(1, sky(&world, vec3(10, 20, 10)));
+        assert_eq!([14, 11, 6], world.get_light(vec3(20, 5, 31)).unwrap()[1..]);
+        // The same, whichever order the chunks are loaded in.
+        for order in [[1, 2, 0], [2, 0, 1]] {
+            let other = world_with(&order.map(|i| (chunks[i].0, chunks[i].1.snapshot())));
+            assert!(all_light(&world) == all_light(&other));
+        }
+    }
+}
@@ -1,12 +1,19 @@
 mod array_3d;
+mod atlas;
 mod camera;
+mod chunk_streaming;
 mod ecosim;
 mod fixed_point;
 mod game_state;
+mod lighting;
+mod material;
 mod physics_world;
+mod raycast;
 mod render_util;
+mod terrain;
 mod texture;
 mod voxel;
+mod voxel_world;
 mod window;
 
 fn main() {
@@ -0,0 +1,132 @@
+use crate::voxel::VoxelType;
+
+pub const AIR: VoxelType = 0;
+pub const DIRT: VoxelType = 1;
+pub const STONE: VoxelType = 2;
+pub const SAND: VoxelType = 3;
+pub const WATER: VoxelType = 4;
+pub const LAMP: VoxelType = 5;
+
+/// Texture names in the atlas, for each side of a voxel.
+pub struct FaceTextures {
+    pub top: &'static str,
+    pub side: &'static str,
+    pub bottom: &'static str,
+}
+
+impl FaceTextures {
+    const fn all(name: &'static str) -> Self {
+        FaceTextures { top: name, side: name, bottom: name }
+    }
+}
+
+/// What a kind of voxel looks like and how it behaves. Voxels store an index into `MATERIALS`.
+#[allow(unused)]
+pub struct Material {
+    pub name: &'static str,
+    /// Whether bodies collide with it.
+    pub is_solid: bool,
+    /// Whether it hides the faces of voxels next to it.
+    pub is_opaque: bool,
+    /// Whether it gets meshed at all.
+    pub is_visible: bool,
+    pub textures: FaceTextures,
+    pub color: [f32; 3],
+    /// Light given off, per channel, from 0 to 15.
+    pub light_emission: [u8; 3],
+    /// Whether flowers can grow on top of it.
+    pub supports_flowers: bool,
+}
+
+pub static MATERIALS: [Material; 6] = [
+    Material {
+        name: "air",
+        is_solid: false,
+        is_opaque: false,
+        is_visible: false,
+        textures: FaceTextures::all(""),
+        color: [0.0, 0.0, 0.0],
+        light_emission: [0, 0, 0],
+        supports_flowers: false,
+    },
+    Material {
+        name: "dirt",
+        is_solid: true,
+        is_opaque: true,
+        is_visible: true,
+        textures: FaceTextures { top: "grass_top", side: "grass_side", bottom: "dirt" },
+        color: [0.62, 0.45, 0.29],
+        light_emission: [0, 0, 0],
+        supports_flowers: true,
+    },
+    Material {
+        name: "stone",
+        is_solid: true,
+        is_opaque: true,
+        is_visible: true,
+        textures: FaceTextures::all("stone"),
+        color: [0.55, 0.55, 0.58],
+        light_emission: [0, 0, 0],
+        supports_flowers: false,
+    },
+    Material {
+        name: "sand",
+        is_solid: true,
+        is_opaque: true,
+        is_visible: true,
+        textures: FaceTextures::all("sand"),
+        color: [1.0, 0.8156863, 0.5019608],
+        light_emission: [0, 0, 0],
+        supports_flowers: false,
+    },
+    Material {
+        name: "water",
+        is_solid: false,
+        is_opaque: false,
+        is_visible: true,
+        textures: FaceTextures::all("water"),
+        color: [0.2, 0.4, 0.8],
+        light_emission: [0, 0, 0],
+        supports_flowers: false,
+    },
+    Material {
+        name: "lamp",
+        is_solid: true,
+        is_opaque: true,
+        is_visible: true,
+        textures: FaceTextures::all("lamp"),
+        color: [1.0, 0.85, 0.55],
+        light_emission: [15, 12, 7],
+        supports_flowers: false,
+    },
+];
+
+/// Panics for a voxel type with no material.
+pub fn material(voxel: VoxelType) -> &'static Material {
+    &MATERIALS[voxel as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids() {
+        let ids = [(AIR, "air"), (DIRT, "dirt"), (STONE, "stone"), (SAND, "sand"), (WATER, "water"), (LAMP, "lamp")];
+        assert_eq!(MATERIALS.len(), ids.len());
+        for (id, name) in ids {
+            assert_eq!(name, material(id).name);
+        }
+    }
+
+    #[test]
+    fn consistent() {
+        for material in MATERIALS.iter() {
+            // Anything opaque or solid has to be drawn, or it would be an invisible wall.
+            assert!(material.is_visible || !(material.is_opaque || material.is_solid), "{}", material.name);
+            let textures = [material.textures.top, material.textures.side, material.textures.bottom];
+            assert!(!material.is_visible || textures.iter().all(|t| !t.is_empty()), "{}", material.name);
+            assert!(material.light_emission.iter().all(|&level| level <= 15), "{}", material.name);
+        }
+    }
+}
@@ -1,13 +1,47 @@
-use cgmath::{Point3, point3, Vector3, vec3};
+use cgmath::{EuclideanSpace, Point3, Vector3, vec3};
 
 use crate::fixed_point::Fixed;
-use crate::voxel::VoxelChunk;
+use crate::material::material;
+use crate::voxel_world::VoxelWorld;
 
+/// How a body interacts with other bodies. All of them collide with voxels.
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CollisionMode {
+    /// Blocks other solid bodies, and reports contacts.
+    Solid,
+    /// Reports contacts with anything it touches or overlaps, but never blocks.
+    Trigger,
+    /// Ignored by other bodies.
+    Ghost,
+}
+
+/// Another body that this one is touching or overlapping, as of the end of the last tick.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Contact {
+    /// Index into the slice passed to `physics_tick`.
+    pub other: usize,
+    /// The axis the bodies meet on, i.e. the one they overlap least on.
+    pub axis: usize,
+}
+
+#[derive(Clone)]
 pub struct PhysicsBody {
     pub position: Point3<Fixed>,
     pub velocity: Vector3<Fixed>,
     pub collision_size: Vector3<Fixed>,
+    pub collision_mode: CollisionMode,
+    /// How high a ledge the body climbs automatically when walking into it on the ground.
+    pub step_height: Fixed,
+    /// The horizontal (x and z) velocity the body is trying to reach, e.g. from player input.
+    pub walk_velocity: Vector3<Fixed>,
+    /// How much the horizontal velocity can change per tick toward `walk_velocity`. Zero means the
+    /// body doesn't walk, and `walk_velocity` is ignored.
+    pub walk_acceleration: Fixed,
+    /// The fraction of `walk_acceleration` the body gets while in the air.
+    pub air_control: Fixed,
     pub is_on_ground: bool,
+    pub contacts: Vec<Contact>,
 }
 
 impl PhysicsBody {
@@ -16,7 +50,13 @@ impl PhysicsBody {
             position: Fixed::ZERO_POINT,
             velocity: Fixed::ZERO_VECTOR,
             collision_size: Fixed::ZERO_VECTOR,
+            collision_mode: CollisionMode::Solid,
+            step_height: Fixed::ZERO,
+            walk_velocity: Fixed::ZERO_VECTOR,
+            walk_acceleration: Fixed::ZERO,
+            air_control: Fixed::ONE,
             is_on_ground: false,
+            contacts: vec![],
         }
     }
 
@@ -24,55 +64,140 @@ impl PhysicsBody {
         self.collision_size.x != Fixed::ZERO && self.collision_size.y != Fixed::ZERO && self.collision_size.z != Fixed::ZERO
     }
 
+    fn blocks_bodies(&self) -> bool {
+        self.has_collision() && self.collision_mode == CollisionMode::Solid
+    }
+
+    fn interacts_with_bodies(&self) -> bool {
+        self.has_collision() && self.collision_mode != CollisionMode::Ghost
+    }
+
     pub fn collision_extent(&self) -> Point3<Fixed> {
-        add_vec_to_point(self.position, add_vec_to_vec(self.collision_size, vec3(-Fixed::EPSILON, -Fixed::EPSILON, -Fixed::EPSILON)))
+        self.position + self.collision_size - vec3(Fixed::EPSILON, Fixed::EPSILON, Fixed::EPSILON)
     }
+
+    /// Whether the body's collision box overlaps the voxel at `coord`.
+    pub fn overlaps_voxel(&self, coord: Vector3<i32>) -> bool {
+        let min = self.position.to_vec().map(physics_to_voxel);
+        let max = self.collision_extent().to_vec().map(physics_to_voxel);
+        self.has_collision() && (0..3).all(|axis| min[axis] <= coord[axis] && coord[axis] <= max[axis])
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OverflowBehavior {
+    /// Overflowing a velocity or position panics, like the `Fixed` operators.
+    Panic,
+    /// Velocities and positions clamp to `Fixed::MIN` and `Fixed::MAX`.
+    Saturate,
+}
+
+/// What bodies see in chunks that aren't loaded.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BoundaryPolicy {
+    /// Unloaded chunks are solid.
+    SolidWalls,
+    /// Unloaded chunks are empty. Bodies that leave the world's bounds get a
+    /// `PhysicsEvent::OutOfWorld` and keep falling.
+    OpenVoid,
+    /// The world's bounds repeat in every direction, and positions wrap back into them. Bodies
+    /// only collide with each other within the same copy of the world.
+    Wrap,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ContactPhase {
+    Started,
+    Ended,
+}
+
+/// Body indices refer to the slice passed to `physics_tick`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PhysicsEvent {
+    /// The body moved entirely outside the world's bounds during the tick.
+    OutOfWorld { body: usize },
+    /// Two bodies started or stopped touching. The lower index comes first.
+    Contact { bodies: (usize, usize), axis: usize, phase: ContactPhase },
+    /// The body would have crossed more than `PhysicsConfig::max_substeps` voxel layers on some
+    /// axis, so it only moved that far. Its velocity is left alone.
+    BudgetExceeded { body: usize },
 }
 
 pub struct PhysicsConfig {
     pub gravity: Vector3<Fixed>,
+    /// The fraction of horizontal velocity bodies lose per tick while on the ground.
+    pub ground_friction: Fixed,
+    /// The fraction of horizontal velocity bodies lose per tick while in the air.
+    pub air_friction: Fixed,
+    /// Velocities are clamped to this on each axis, in either direction, after gravity is applied.
+    pub max_speed: Vector3<Fixed>,
+    /// The most voxel layers a body may move into on one axis in one tick. This bounds the work
+    /// done per body per tick regardless of its speed.
+    pub max_substeps: u32,
+    pub overflow_behavior: OverflowBehavior,
+    pub boundary_policy: BoundaryPolicy,
+}
+
+impl PhysicsConfig {
+    fn add(&self, lhs: Fixed, rhs: Fixed) -> Fixed {
+        match self.overflow_behavior {
+            OverflowBehavior::Panic => lhs + rhs,
+            OverflowBehavior::Saturate => lhs.saturating_add(rhs),
+        }
+    }
+
+    fn add_vec(&self, lhs: Vector3<Fixed>, rhs: Vector3<Fixed>) -> Vector3<Fixed> {
+        vec3(self.add(lhs.x, rhs.x), self.add(lhs.y, rhs.y), self.add(lhs.z, rhs.z))
+    }
 }
 
 impl Default for PhysicsConfig {
     fn default() -> Self {
         PhysicsConfig {
             gravity: Fixed::ZERO_VECTOR,
+            ground_friction: Fixed::ZERO,
+            air_friction: Fixed::ZERO,
+            max_speed: vec3(Fixed::MAX, Fixed::MAX, Fixed::MAX),
+            max_substeps: 64,
+            overflow_behavior: OverflowBehavior::Panic,
+            boundary_policy: BoundaryPolicy::SolidWalls,
         }
     }
 }
 
-// TODO: Looks like cgmath requires you implement *all* the math functions on the inner type before
-// you can use the vector math functions. So we should only need these custom add functions until
-// we get around to implementing multiplication and division.
-
-fn add_vec_to_vec(v1: Vector3<Fixed>, v2: Vector3<Fixed>) -> Vector3<Fixed> {
-    vec3(v1.x + v2.x, v1.y + v2.y, v1.z + v2.z)
+fn physics_to_voxel(component: Fixed) -> i32 {
+    component.floor_i32()
 }
 
-fn add_vec_to_point(p: Point3<Fixed>, v: Vector3<Fixed>) -> Point3<Fixed> {
-    point3(p.x + v.x, p.y + v.y, p.z + v.z)
+// Where a layer of voxels starts, clamped to the range `Fixed` can hold.
+fn voxel_to_physics(layer: i64) -> Fixed {
+    let max_layer = Fixed::MAX.trunc_i32() as i64;
+    Fixed::new(layer.clamp(-max_layer, max_layer) as i32, 0)
 }
 
-fn physics_to_voxel(component: Fixed) -> Option<usize> {
-    let f = component.to_f32().floor();
-    if f >= 0.0 { Some(f as usize) } else { None }
+fn is_voxel_solid(config: &PhysicsConfig, voxels: &VoxelWorld, coord: Vector3<i32>) -> bool {
+    if let Some(voxel) = voxels.get_voxel(coord) {
+        return material(voxel).is_solid;
+    }
+    match config.boundary_policy {
+        BoundaryPolicy::SolidWalls => true,
+        BoundaryPolicy::OpenVoid => false,
+        BoundaryPolicy::Wrap => {
+            // Gaps inside the bounds are empty, or this would never return.
+            let Some((min, max)) = voxels.voxel_bounds() else {
+                return false;
+            };
+            let wrapped = vec3(0usize, 1, 2).map(|axis| min[axis] + (coord[axis] - min[axis]).rem_euclid(max[axis] - min[axis]));
+            voxels.get_voxel(wrapped).is_some_and(|voxel| material(voxel).is_solid)
+        }
+    }
 }
 
-fn is_body_colliding_with_voxels(body: &PhysicsBody, voxels: &VoxelChunk) -> bool {
-    if !body.has_collision() {
-        return false;
-    }
-    let min_voxel_x = physics_to_voxel(body.position.x).unwrap_or(0);
-    let min_voxel_y = physics_to_voxel(body.position.y).unwrap_or(0);
-    let min_voxel_z = physics_to_voxel(body.position.z).unwrap_or(0);
-    let extent = body.collision_extent();
-    let max_voxel_x = physics_to_voxel(extent.x).unwrap_or(0);
-    let max_voxel_y = physics_to_voxel(extent.y).unwrap_or(0);
-    let max_voxel_z = physics_to_voxel(extent.z).unwrap_or(0);
-    for x in min_voxel_x..=max_voxel_x {
-        for y in min_voxel_y..=max_voxel_y {
-            for z in min_voxel_z..=max_voxel_z {
-                if voxels.get_voxel(vec3(x, y, z)) != 0 {
+fn is_region_solid(config: &PhysicsConfig, voxels: &VoxelWorld, min: Vector3<i32>, max: Vector3<i32>) -> bool {
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                if is_voxel_solid(config, voxels, vec3(x, y, z)) {
                     return true;
                 }
             }
@@ -81,115 +206,356 @@ fn is_body_colliding_with_voxels(body: &PhysicsBody, voxels: &VoxelChunk) -> boo
     false
 }
 
-// TODO: Walking off the edge of the chunk in the positive direction panics.
-// TODO: Walking off the edge of the chunk in the negative direction doesn't apply gravity.
-// TODO: Probably should cap velocity to avoid arbitrarily large tick loops.
+fn is_outside_world(body: &PhysicsBody, voxels: &VoxelWorld) -> bool {
+    let Some((world_min, world_max)) = voxels.voxel_bounds() else {
+        return true;
+    };
+    let min = body.position.to_vec().map(physics_to_voxel);
+    // A body without a collision size still occupies its position.
+    let max = body.collision_extent().to_vec().map(physics_to_voxel).zip(min, i32::max);
+    (0..3).any(|axis| max[axis] < world_min[axis] || min[axis] >= world_max[axis])
+}
 
-pub fn physics_tick(config: &PhysicsConfig, bodies: &mut [PhysicsBody], voxels: &VoxelChunk) {
-    for body in bodies.iter_mut() {
-        body.is_on_ground = false;
-        body.velocity = add_vec_to_vec(body.velocity, config.gravity);
-        for _x in 0..body.velocity.x.epsilons() {
-            let previous = body.position.x;
-            body.position.x += if body.velocity.x.is_negative() { -Fixed::EPSILON } else { Fixed::EPSILON };
-            if is_body_colliding_with_voxels(body, voxels) {
-                body.position.x = previous;
-                body.velocity.x = Fixed::ZERO;
-                break;
+fn wrap_position(position: Fixed, min: i32, max: i32) -> Fixed {
+    let (min, size) = (Fixed::new(min, 0), Fixed::new(max - min, 0));
+    let wrapped = (position - min) % size;
+    min + if wrapped.is_negative() { wrapped + size } else { wrapped }
+}
+
+// Finds how far a body can move along one axis before it overlaps a solid voxel. Rather than
+// stepping, this visits each layer of cells the body would enter, in order, so the cost depends on
+// the number of cells crossed rather than the speed. Returns `None` if nothing is in the way.
+//
+// The result matches moving one epsilon at a time and stopping before the first overlapping
+// position. That includes a body that already overlaps something not moving at all.
+fn sweep_axis(config: &PhysicsConfig, body: &PhysicsBody, voxels: &VoxelWorld, axis: usize, distance: Fixed) -> Option<Fixed> {
+    if distance == Fixed::ZERO || !body.has_collision() {
+        return None;
+    }
+    let position = body.position[axis];
+    let extent = body.collision_extent()[axis];
+    let mut min = body.position.to_vec().map(physics_to_voxel);
+    let mut max = body.collision_extent().to_vec().map(physics_to_voxel);
+    let forward = !distance.is_negative();
+    let cells: Vec<i32> = if forward {
+        (physics_to_voxel(position.saturating_add(Fixed::EPSILON))..=physics_to_voxel(extent.saturating_add(distance))).collect()
+    } else {
+        (physics_to_voxel(position.saturating_add(distance))..=physics_to_voxel(extent.saturating_sub(Fixed::EPSILON))).rev().collect()
+    };
+    for cell in cells {
+        min[axis] = cell;
+        max[axis] = cell;
+        if is_region_solid(config, voxels, min, max) {
+            // Move up to the edge of the cell, unless the body already overlaps it.
+            return Some(if forward {
+                (Fixed::new(cell, 0) - Fixed::EPSILON - extent).max(Fixed::ZERO)
+            } else {
+                -(position - Fixed::new(cell + 1, 0)).max(Fixed::ZERO)
+            });
+        }
+    }
+    None
+}
+
+// Like `sweep_axis`, but against the other solid bodies in `candidates`. Bodies that already
+// overlap ignore each other, so that they can separate.
+fn sweep_bodies(body: &PhysicsBody, bodies: &[PhysicsBody], candidates: &[usize], axis: usize, distance: Fixed) -> Option<Fixed> {
+    if distance == Fixed::ZERO || !body.blocks_bodies() {
+        return None;
+    }
+    let (min, max) = (body.position, body.collision_extent());
+    let mut blocked: Option<Fixed> = None;
+    for other in candidates.iter().map(|&i| &bodies[i]).filter(|other| other.blocks_bodies()) {
+        let (other_min, other_max) = (other.position, other.collision_extent());
+        let overlaps = |a: usize| min[a] <= other_max[a] && other_min[a] <= max[a];
+        if (0..3).filter(|&a| a != axis).any(|a| !overlaps(a)) || overlaps(axis) {
+            continue;
+        }
+        let gap = if distance.is_negative() {
+            if other_max[axis] > min[axis] {
+                continue;
             }
+            -(min[axis] - other_max[axis] - Fixed::EPSILON)
+        } else {
+            if other_min[axis] < max[axis] {
+                continue;
+            }
+            other_min[axis] - max[axis] - Fixed::EPSILON
+        };
+        if gap.abs() < blocked.unwrap_or(distance).abs() {
+            blocked = Some(gap);
         }
-        for _y in 0..body.velocity.y.epsilons() {
-            let previous = body.position.y;
-            body.position.y += if body.velocity.y.is_negative() { -Fixed::EPSILON } else { Fixed::EPSILON };
-            if is_body_colliding_with_voxels(body, voxels) {
-                body.position.y = previous;
-                body.velocity.y = Fixed::ZERO;
-                body.is_on_ground = true;
+    }
+    blocked
+}
+
+// The box a body could cover during this tick, grown by an epsilon so that touching counts.
+fn swept_bounds(body: &PhysicsBody) -> (Vector3<Fixed>, Vector3<Fixed>) {
+    let min = body.position.to_vec();
+    let max = body.collision_extent().to_vec();
+    (
+        min.zip(body.velocity, |m, v| m.min(m.saturating_add(v)).saturating_sub(Fixed::EPSILON)),
+        max.zip(body.velocity, |m, v| m.max(m.saturating_add(v)).saturating_add(Fixed::EPSILON)),
+    )
+}
+
+// Sort-and-sweep: orders the bodies by the low x edge of their swept bounds, then only compares
+// bodies whose x ranges overlap. Returns each pair that might touch this tick, lower index first.
+fn broadphase(bodies: &[PhysicsBody]) -> Vec<(usize, usize)> {
+    let bounds: Vec<_> = bodies.iter().map(swept_bounds).collect();
+    let mut order: Vec<usize> = (0..bodies.len()).filter(|&i| bodies[i].interacts_with_bodies()).collect();
+    order.sort_by_key(|&i| bounds[i].0.x);
+    let mut pairs = vec![];
+    for (n, &a) in order.iter().enumerate() {
+        for &b in &order[n + 1..] {
+            if bounds[b].0.x > bounds[a].1.x {
                 break;
             }
+            if (1..3).all(|axis| bounds[a].0[axis] <= bounds[b].1[axis] && bounds[b].0[axis] <= bounds[a].1[axis]) {
+                pairs.push((a.min(b), a.max(b)));
+            }
         }
-        for _z in 0..body.velocity.z.epsilons() {
-            let previous = body.position.z;
-            body.position.z += if body.velocity.z.is_negative() { -Fixed::EPSILON } else { Fixed::EPSILON };
-            if is_body_colliding_with_voxels(body, voxels) {
-                body.position.z = previous;
-                body.velocity.z = Fixed::ZERO;
-                break;
+    }
+    pairs
+}
+
+// Bodies are in contact when their boxes touch or overlap. The contact axis is the one with the
+// least overlap, which is the axis they touch on when they're just touching.
+fn contact_axis(a: &PhysicsBody, b: &PhysicsBody) -> Option<usize> {
+    let (a_max, b_max) = (a.collision_extent(), b.collision_extent());
+    let overlap = |axis: usize| a_max[axis].min(b_max[axis]) - a.position[axis].max(b.position[axis]);
+    if (0..3).any(|axis| overlap(axis) < -Fixed::EPSILON) {
+        return None;
+    }
+    (0..3).min_by_key(|&axis| overlap(axis))
+}
+
+fn update_contacts(bodies: &mut [PhysicsBody], pairs: &[(usize, usize)], events: &mut Vec<PhysicsEvent>) {
+    let mut contacts = vec![vec![]; bodies.len()];
+    for &(a, b) in pairs {
+        if let Some(axis) = contact_axis(&bodies[a], &bodies[b]) {
+            contacts[a].push(Contact { other: b, axis });
+            contacts[b].push(Contact { other: a, axis });
+        }
+    }
+    for (index, (body, new_contacts)) in bodies.iter_mut().zip(contacts).enumerate() {
+        for (from, to, phase) in [(&body.contacts, &new_contacts, ContactPhase::Ended), (&new_contacts, &body.contacts, ContactPhase::Started)] {
+            for contact in from.iter().filter(|c| c.other > index && !to.iter().any(|d| d.other == c.other)) {
+                events.push(PhysicsEvent::Contact { bodies: (index, contact.other), axis: contact.axis, phase });
             }
         }
+        body.contacts = new_contacts;
     }
 }
 
+// Shortens a move so that the body enters at most `max_substeps` new layers of cells. Returns the
+// allowed distance, and whether it had to be shortened.
+fn limit_to_budget(config: &PhysicsConfig, body: &PhysicsBody, axis: usize, distance: Fixed) -> (Fixed, bool) {
+    if !body.has_collision() {
+        return (distance, false);
+    }
+    let max_substeps = config.max_substeps as i64;
+    if distance.is_negative() {
+        let position = body.position[axis];
+        let first = physics_to_voxel(position) as i64;
+        if first - physics_to_voxel(position.saturating_add(distance)) as i64 <= max_substeps {
+            return (distance, false);
+        }
+        (voxel_to_physics(first - max_substeps).saturating_sub(position), true)
+    } else {
+        let extent = body.collision_extent()[axis];
+        let last = physics_to_voxel(extent) as i64;
+        if physics_to_voxel(extent.saturating_add(distance)) as i64 - last <= max_substeps {
+            return (distance, false);
+        }
+        (voxel_to_physics(last + max_substeps + 1).saturating_sub(Fixed::EPSILON).saturating_sub(extent), true)
+    }
+}
+
+// The nearest of `sweep_axis` and `sweep_bodies`.
+fn sweep(config: &PhysicsConfig, voxels: &VoxelWorld, bodies: &[PhysicsBody], candidates: &[usize], body: &PhysicsBody, axis: usize, distance: Fixed) -> Option<Fixed> {
+    [
+        sweep_axis(config, body, voxels, axis, distance),
+        sweep_bodies(body, bodies, candidates, axis, distance),
+    ].into_iter().flatten().min_by_key(|distance| distance.abs())
+}
+
+// Friction, then acceleration toward the walk velocity. Both only affect x and z.
+fn apply_walking(config: &PhysicsConfig, body: &mut PhysicsBody) {
+    let (friction, control) = if body.is_on_ground {
+        (config.ground_friction, Fixed::ONE)
+    } else {
+        (config.air_friction, body.air_control)
+    };
+    body.velocity.x *= Fixed::ONE - friction;
+    body.velocity.z *= Fixed::ONE - friction;
+    if body.walk_acceleration == Fixed::ZERO {
+        return;
+    }
+    let acceleration = body.walk_acceleration * control;
+    let difference = vec3(body.walk_velocity.x - body.velocity.x, Fixed::ZERO, body.walk_velocity.z - body.velocity.z);
+    if Fixed::vector3_magnitude(difference) <= acceleration {
+        body.velocity += difference;
+    } else {
+        body.velocity += Fixed::vector3_normalize(difference) * acceleration;
+    }
+}
+
+// Called when a grounded body is blocked moving horizontally. Tries the same move after lifting the
+// body by up to its step height, then sets it back down. Returns the stepped body and what blocked
+// it; the caller decides whether that got further.
+fn step_up(config: &PhysicsConfig, voxels: &VoxelWorld, bodies: &[PhysicsBody], candidates: &[usize], body: &PhysicsBody, axis: usize, distance: Fixed) -> (PhysicsBody, Option<Fixed>) {
+    let mut raised = body.clone();
+    let up = sweep(config, voxels, bodies, candidates, &raised, 1, body.step_height).unwrap_or(body.step_height);
+    raised.position.y += up;
+    let raised_blocked = sweep(config, voxels, bodies, candidates, &raised, axis, distance);
+    raised.position[axis] += raised_blocked.unwrap_or(distance);
+    raised.position.y += sweep(config, voxels, bodies, candidates, &raised, 1, -up).unwrap_or(-up);
+    (raised, raised_blocked)
+}
+
+pub fn physics_tick(config: &PhysicsConfig, bodies: &mut [PhysicsBody], voxels: &VoxelWorld) -> Vec<PhysicsEvent> {
+    let mut events = vec![];
+    let mut was_on_ground = vec![false; bodies.len()];
+    for (index, body) in bodies.iter_mut().enumerate() {
+        apply_walking(config, body);
+        was_on_ground[index] = body.is_on_ground;
+        body.is_on_ground = false;
+        body.velocity = config.add_vec(body.velocity, config.gravity);
+        body.velocity = body.velocity.zip(config.max_speed, |v, max| v.clamp(-max, max));
+    }
+    let pairs = broadphase(bodies);
+    let mut candidates = vec![vec![]; bodies.len()];
+    for &(a, b) in &pairs {
+        candidates[a].push(b);
+        candidates[b].push(a);
+    }
+    for index in 0..bodies.len() {
+        let was_outside = is_outside_world(&bodies[index], voxels);
+        let mut budget_exceeded = false;
+        for axis in 0..3 {
+            let body = &bodies[index];
+            let velocity = body.velocity[axis];
+            let (distance, exceeded) = limit_to_budget(config, body, axis, velocity);
+            let mut blocked = sweep(config, voxels, bodies, &candidates[index], body, axis, distance);
+            let mut position = body.position;
+            match blocked {
+                Some(allowed) if axis != 1 && was_on_ground[index] && body.step_height > Fixed::ZERO => {
+                    let (stepped, stepped_blocked) = step_up(config, voxels, bodies, &candidates[index], body, axis, distance);
+                    if (stepped.position[axis] - position[axis]).abs() > allowed.abs() {
+                        position = stepped.position;
+                        blocked = stepped_blocked;
+                    } else {
+                        position[axis] = config.add(position[axis], allowed);
+                    }
+                }
+                _ => position[axis] = config.add(position[axis], blocked.unwrap_or(distance)),
+            }
+            budget_exceeded |= exceeded && blocked.is_none();
+            let body = &mut bodies[index];
+            body.position = position;
+            if config.boundary_policy == BoundaryPolicy::Wrap && let Some((min, max)) = voxels.voxel_bounds() {
+                body.position[axis] = wrap_position(body.position[axis], min[axis], max[axis]);
+            }
+            if blocked.is_some() {
+                body.velocity[axis] = Fixed::ZERO;
+                if axis == 1 && velocity.is_negative() {
+                    body.is_on_ground = true;
+                }
+            }
+        }
+        if !was_outside && is_outside_world(&bodies[index], voxels) {
+            events.push(PhysicsEvent::OutOfWorld { body: index });
+        }
+        if budget_exceeded {
+            events.push(PhysicsEvent::BudgetExceeded { body: index });
+        }
+    }
+    update_contacts(bodies, &pairs, &mut events);
+    events
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use cgmath::point3;
+
+    use crate::voxel::{CHUNK_SIZE, VoxelChunk};
+
+    // An empty chunk at the origin, with nothing else loaded.
+    fn one_chunk() -> VoxelWorld {
+        let mut world = VoxelWorld::new();
+        world.insert_chunk(vec3(0, 0, 0), VoxelChunk::new());
+        world
+    }
+
     #[test]
     fn basic_movement() {
         let config = PhysicsConfig::default();
         let mut bodies = vec![ PhysicsBody::new() ];
-        let voxel_chunk = VoxelChunk::new();
+        let world = one_chunk();
 
         assert_eq!(Fixed::ZERO_POINT, bodies[0].position);
         assert_eq!(Fixed::ZERO_VECTOR, bodies[0].velocity);
 
         bodies[0].velocity.x = Fixed::new(1, 0);
-        physics_tick(&config, &mut bodies, &voxel_chunk);
+        physics_tick(&config, &mut bodies, &world);
         assert_eq!(point3(Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO), bodies[0].position);
 
         bodies[0].velocity.x = Fixed::ZERO;
         bodies[0].velocity.y = Fixed::new(0, 128);
-        physics_tick(&config, &mut bodies, &voxel_chunk);
+        physics_tick(&config, &mut bodies, &world);
         assert_eq!(point3(Fixed::new(1, 0), Fixed::new(0, 128), Fixed::ZERO), bodies[0].position);
     }
 
     #[test]
     fn gravity() {
-        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, Fixed::new(-2, 0), Fixed::ZERO) };
+        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, Fixed::new(-2, 0), Fixed::ZERO), ..Default::default() };
         let mut bodies = vec![ PhysicsBody::new() ];
-        let voxel_chunk = VoxelChunk::new();
+        let world = one_chunk();
 
         let ys = [-2, -6, -12, -20];
         for &y in ys.iter() {
-            physics_tick(&config, &mut bodies, &voxel_chunk);
+            physics_tick(&config, &mut bodies, &world);
             assert_eq!(point3(Fixed::ZERO, Fixed::new(y, 0), Fixed::ZERO), bodies[0].position);
         }
     }
 
     #[test]
     fn test_physics_to_voxel() {
-        assert_eq!(Some(1), physics_to_voxel(Fixed::new(1, 0)));
-        assert_eq!(Some(0), physics_to_voxel(Fixed::new(0, 255)));
-        assert_eq!(None, physics_to_voxel(-Fixed::new(0, 1)));
+        assert_eq!(1, physics_to_voxel(Fixed::new(1, 0)));
+        assert_eq!(0, physics_to_voxel(Fixed::new(0, 255)));
+        assert_eq!(-1, physics_to_voxel(-Fixed::new(0, 1)));
     }
 
     #[test]
     fn fall_onto_ground() {
-        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, -Fixed::new(0, 64), Fixed::ZERO) };
+        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, -Fixed::new(0, 64), Fixed::ZERO), ..Default::default() };
         let mut bodies = vec![ PhysicsBody::new() ];
-        let mut voxel_chunk = VoxelChunk::new();
+        let mut world = one_chunk();
 
         bodies[0].position = point3(Fixed::ZERO, Fixed::new(2, 0), Fixed::ZERO);
         bodies[0].collision_size = vec3(Fixed::new(0, 64), Fixed::new(0, 64), Fixed::new(0, 64));
-        voxel_chunk.set_voxel(vec3(0, 0, 0), 1);
+        world.set_voxel(vec3(0, 0, 0), 1);
 
         let ys = [Fixed::new(1, 192), Fixed::new(1, 64), Fixed::new(1, 0), Fixed::new(1, 0)];
         for &y in ys.iter() {
-            physics_tick(&config, &mut bodies, &voxel_chunk);
+            physics_tick(&config, &mut bodies, &world);
             assert_eq!(point3(Fixed::ZERO, y, Fixed::ZERO), bodies[0].position);
         }
     }
 
     #[test]
     fn run_off_an_edge() {
-        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, -Fixed::new(0, 64), Fixed::ZERO) };
+        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, -Fixed::new(0, 64), Fixed::ZERO), ..Default::default() };
         let mut bodies = vec![ PhysicsBody::new() ];
-        let mut voxel_chunk = VoxelChunk::new();
+        let mut world = one_chunk();
 
         bodies[0].position = point3(Fixed::ZERO, Fixed::new(1, 0), Fixed::ZERO);
         bodies[0].velocity = vec3(Fixed::new(0, 86), Fixed::ZERO, Fixed::ZERO);
         bodies[0].collision_size = vec3(Fixed::new(0, 64), Fixed::new(0, 64), Fixed::new(0, 64));
-        voxel_chunk.set_voxel(vec3(0, 0, 0), 1);
+        world.set_voxel(vec3(0, 0, 0), 1);
 
         let xys = [
             (Fixed::new(0, 86), Fixed::new(1, 0)),
@@ -198,7 +564,7 @@ mod tests {
             (Fixed::new(1, 88), Fixed::new(0, 64)),
         ];
         for &(x, y) in xys.iter() {
-            physics_tick(&config, &mut bodies, &voxel_chunk);
+            physics_tick(&config, &mut bodies, &world);
             assert_eq!(point3(x, y, Fixed::ZERO), bodies[0].position);
         }
     }
@@ -207,14 +573,14 @@ mod tests {
     fn run_into_wall() {
         let config = PhysicsConfig::default();
         let mut bodies = vec![ PhysicsBody::new() ];
-        let mut voxel_chunk = VoxelChunk::new();
+        let mut world = one_chunk();
 
         bodies[0].collision_size = vec3(Fixed::new(0, 64), Fixed::new(0, 64), Fixed::new(0, 64));
         bodies[0].velocity = vec3(Fixed::new(0, 128), Fixed::ZERO, Fixed::new(0, 128));
-        voxel_chunk.set_voxel(vec3(2, 0, 1), 1);
-        voxel_chunk.set_voxel(vec3(2, 0, 2), 1);
-        voxel_chunk.set_voxel(vec3(2, 0, 3), 1);
-        voxel_chunk.set_voxel(vec3(1, 0, 3), 1);
+        world.set_voxel(vec3(2, 0, 1), 1);
+        world.set_voxel(vec3(2, 0, 2), 1);
+        world.set_voxel(vec3(2, 0, 3), 1);
+        world.set_voxel(vec3(1, 0, 3), 1);
 
         let xzs = [
             (Fixed::new(0, 128), Fixed::new(0, 128)),
@@ -226,8 +592,517 @@ mod tests {
             (Fixed::new(1, 192), Fixed::new(2, 192)),
         ];
         for &(x, z) in xzs.iter() {
-            physics_tick(&config, &mut bodies, &voxel_chunk);
+            physics_tick(&config, &mut bodies, &world);
             assert_eq!(point3(x, Fixed::ZERO, z), bodies[0].position);
         }
     }
+
+    #[test]
+    fn saturating_overflow() {
+        let config = PhysicsConfig { overflow_behavior: OverflowBehavior::Saturate, ..Default::default() };
+        let mut bodies = vec![ PhysicsBody::new() ];
+        let world = one_chunk();
+
+        bodies[0].position.x = Fixed::MAX - Fixed::EPSILON;
+        bodies[0].velocity.x = Fixed::new(0, 2);
+        physics_tick(&config, &mut bodies, &world);
+        assert_eq!(Fixed::MAX, bodies[0].position.x);
+    }
+
+    #[test]
+    fn fast_body_stops_at_wall() {
+        let config = PhysicsConfig::default();
+        let mut bodies = vec![ PhysicsBody::new() ];
+        let mut world = one_chunk();
+
+        bodies[0].position = point3(Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO);
+        bodies[0].collision_size = vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128));
+        bodies[0].velocity = vec3(Fixed::new(20, 0), Fixed::ZERO, Fixed::ZERO);
+        world.set_voxel(vec3(9, 0, 0), 1);
+
+        physics_tick(&config, &mut bodies, &world);
+        assert_eq!(point3(Fixed::new(8, 128), Fixed::ZERO, Fixed::ZERO), bodies[0].position);
+        assert_eq!(Fixed::ZERO_VECTOR, bodies[0].velocity);
+
+        bodies[0].velocity = vec3(-Fixed::new(20, 0), Fixed::ZERO, Fixed::ZERO);
+        world.set_voxel(vec3(2, 0, 0), 1);
+        physics_tick(&config, &mut bodies, &world);
+        assert_eq!(point3(Fixed::new(3, 0), Fixed::ZERO, Fixed::ZERO), bodies[0].position);
+    }
+
+    #[test]
+    fn collides_across_chunks() {
+        let config = PhysicsConfig { boundary_policy: BoundaryPolicy::OpenVoid, ..Default::default() };
+        let mut bodies = vec![ PhysicsBody::new() ];
+        let mut world = one_chunk();
+        world.set_voxel(vec3(-3, 0, 0), 1);
+
+        bodies[0].position = point3(Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO);
+        bodies[0].collision_size = vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128));
+        bodies[0].velocity = vec3(-Fixed::new(8, 0), Fixed::ZERO, Fixed::ZERO);
+        assert!(physics_tick(&config, &mut bodies, &world).is_empty());
+        assert_eq!(point3(-Fixed::new(2, 0), Fixed::ZERO, Fixed::ZERO), bodies[0].position);
+    }
+
+    #[test]
+    fn overlapping_body_does_not_move() {
+        let config = PhysicsConfig::default();
+        let mut bodies = vec![ PhysicsBody::new() ];
+        let mut world = one_chunk();
+
+        bodies[0].position = point3(Fixed::new(1, 128), Fixed::ZERO, Fixed::ZERO);
+        bodies[0].collision_size = vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128));
+        bodies[0].velocity = vec3(Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO);
+        world.set_voxel(vec3(1, 0, 0), 1);
+
+        physics_tick(&config, &mut bodies, &world);
+        assert_eq!(point3(Fixed::new(1, 128), Fixed::ZERO, Fixed::ZERO), bodies[0].position);
+        assert_eq!(Fixed::ZERO, bodies[0].velocity.x);
+    }
+
+    #[test]
+    fn ceiling_is_not_ground() {
+        let config = PhysicsConfig::default();
+        let mut bodies = vec![ PhysicsBody::new() ];
+        let mut world = one_chunk();
+
+        bodies[0].collision_size = vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128));
+        bodies[0].velocity = vec3(Fixed::ZERO, Fixed::new(2, 0), Fixed::ZERO);
+        world.set_voxel(vec3(0, 1, 0), 1);
+
+        physics_tick(&config, &mut bodies, &world);
+        assert_eq!(point3(Fixed::ZERO, Fixed::new(0, 128), Fixed::ZERO), bodies[0].position);
+        assert!(!bodies[0].is_on_ground);
+    }
+
+    #[test]
+    fn solid_walls() {
+        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, -Fixed::new(0, 64), Fixed::ZERO), ..Default::default() };
+        let mut bodies = vec![ PhysicsBody::new() ];
+        let world = one_chunk();
+
+        bodies[0].position = point3(Fixed::new(30, 0), Fixed::new(0, 32), Fixed::new(1, 0));
+        bodies[0].collision_size = vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128));
+        bodies[0].velocity = vec3(Fixed::new(4, 0), Fixed::ZERO, -Fixed::new(4, 0));
+        let events = physics_tick(&config, &mut bodies, &world);
+        assert_eq!(point3(Fixed::new(31, 128), Fixed::ZERO, Fixed::ZERO), bodies[0].position);
+        assert!(bodies[0].is_on_ground);
+        assert_eq!(Vec::<PhysicsEvent>::new(), events);
+    }
+
+    #[test]
+    fn open_void() {
+        let config = PhysicsConfig {
+            gravity: vec3(Fixed::ZERO, -Fixed::new(1, 0), Fixed::ZERO),
+            boundary_policy: BoundaryPolicy::OpenVoid,
+            ..Default::default()
+        };
+        let mut bodies = vec![ PhysicsBody::new(), PhysicsBody::new() ];
+        let mut world = one_chunk();
+        world.set_voxel(vec3(0, 0, 0), 1);
+
+        for body in bodies.iter_mut() {
+            body.position = point3(Fixed::ZERO, Fixed::new(1, 0), Fixed::ZERO);
+            body.collision_size = vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128));
+        }
+        bodies[1].velocity.x = -Fixed::new(1, 0);
+
+        // The first body rests on the voxel. The second walks off the negative edge and falls.
+        assert_eq!(vec![PhysicsEvent::OutOfWorld { body: 1 }], physics_tick(&config, &mut bodies, &world));
+        assert_eq!(point3(Fixed::ZERO, Fixed::new(1, 0), Fixed::ZERO), bodies[0].position);
+        assert_eq!(point3(-Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO), bodies[1].position);
+
+        assert_eq!(Vec::<PhysicsEvent>::new(), physics_tick(&config, &mut bodies, &world));
+        assert_eq!(-Fixed::new(2, 0), bodies[1].position.y);
+        assert_eq!(-Fixed::new(2, 0), bodies[1].velocity.y);
+    }
+
+    #[test]
+    fn wrap() {
+        let config = PhysicsConfig { boundary_policy: BoundaryPolicy::Wrap, ..Default::default() };
+        let mut bodies = vec![ PhysicsBody::new() ];
+        let mut world = one_chunk();
+
+        bodies[0].position = point3(Fixed::new(0, 64), Fixed::new(4, 0), Fixed::new(4, 0));
+        bodies[0].collision_size = vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128));
+        bodies[0].velocity = vec3(-Fixed::new(0, 128), Fixed::ZERO, Fixed::ZERO);
+        physics_tick(&config, &mut bodies, &world);
+        assert_eq!(point3(Fixed::new(31, 192), Fixed::new(4, 0), Fixed::new(4, 0)), bodies[0].position);
+
+        // A voxel just past the seam blocks the body from the other side.
+        world.set_voxel(vec3(1, 4, 4), 1);
+        bodies[0].velocity = vec3(Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO);
+        physics_tick(&config, &mut bodies, &world);
+        assert_eq!(point3(Fixed::new(0, 128), Fixed::new(4, 0), Fixed::new(4, 0)), bodies[0].position);
+        assert_eq!(Fixed::ZERO, bodies[0].velocity.x);
+    }
+
+    #[test]
+    fn friction() {
+        let config = PhysicsConfig {
+            gravity: vec3(Fixed::ZERO, -Fixed::new(0, 1), Fixed::ZERO),
+            ground_friction: Fixed::new(0, 128),
+            air_friction: Fixed::new(0, 64),
+            ..Default::default()
+        };
+        let mut bodies = vec![ PhysicsBody::new(), PhysicsBody::new() ];
+        let mut world = one_chunk();
+        for i in 0..CHUNK_SIZE.x as i32 {
+            world.set_voxel(vec3(i, 0, 0), 1);
+        }
+
+        bodies[0].position = point3(Fixed::ZERO, Fixed::new(1, 0), Fixed::ZERO);
+        bodies[0].is_on_ground = true;
+        bodies[1].position = point3(Fixed::ZERO, Fixed::new(4, 0), Fixed::ZERO);
+        for body in bodies.iter_mut() {
+            body.collision_size = vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128));
+            body.velocity.x = Fixed::new(1, 0);
+        }
+
+        let velocities = [(128, 192), (64, 144), (32, 108), (16, 81)];
+        for &(ground, air) in velocities.iter() {
+            physics_tick(&config, &mut bodies, &world);
+            assert_eq!(Fixed::new(0, ground), bodies[0].velocity.x);
+            assert_eq!(Fixed::new(0, air), bodies[1].velocity.x);
+        }
+        for _ in 0..5 {
+            physics_tick(&config, &mut bodies, &world);
+        }
+        assert_eq!(Fixed::ZERO, bodies[0].velocity.x);
+    }
+
+    #[test]
+    fn walk_acceleration() {
+        let config = PhysicsConfig::default();
+        let mut bodies = vec![ PhysicsBody::new(), PhysicsBody::new() ];
+        let world = one_chunk();
+
+        for body in bodies.iter_mut() {
+            body.walk_velocity = vec3(Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO);
+            body.walk_acceleration = Fixed::new(0, 64);
+            body.air_control = Fixed::new(0, 128);
+        }
+        bodies[0].is_on_ground = true;
+
+        // Ground is never touched, so the first body only gets full acceleration on the first tick.
+        let velocities = [(64, 32), (96, 64), (128, 96)];
+        for &(first, second) in velocities.iter() {
+            physics_tick(&config, &mut bodies, &world);
+            assert_eq!(Fixed::new(0, first), bodies[0].velocity.x);
+            assert_eq!(Fixed::new(0, second), bodies[1].velocity.x);
+        }
+        for _ in 0..8 {
+            physics_tick(&config, &mut bodies, &world);
+        }
+        assert_eq!(Fixed::new(1, 0), bodies[1].velocity.x);
+
+        // The target velocity is reached exactly, and the body slows back down toward zero.
+        bodies[1].walk_velocity = Fixed::ZERO_VECTOR;
+        physics_tick(&config, &mut bodies, &world);
+        assert_eq!(Fixed::new(0, 224), bodies[1].velocity.x);
+    }
+
+    fn ledge(height: i32) -> VoxelWorld {
+        let mut world = one_chunk();
+        for i in 0..8 {
+            world.set_voxel(vec3(i, 0, 0), 1);
+        }
+        for j in 1..=height {
+            world.set_voxel(vec3(3, j, 0), 1);
+        }
+        world
+    }
+
+    fn walker(step_height: Fixed) -> PhysicsBody {
+        PhysicsBody {
+            position: point3(Fixed::new(2, 0), Fixed::new(1, 0), Fixed::ZERO),
+            velocity: vec3(Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO),
+            collision_size: vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128)),
+            step_height,
+            is_on_ground: true,
+            ..PhysicsBody::new()
+        }
+    }
+
+    #[test]
+    fn step_up() {
+        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, -Fixed::new(0, 64), Fixed::ZERO), ..Default::default() };
+
+        let mut bodies = vec![ walker(Fixed::new(1, 0)) ];
+        physics_tick(&config, &mut bodies, &ledge(1));
+        assert_eq!(point3(Fixed::new(3, 0), Fixed::new(2, 0), Fixed::ZERO), bodies[0].position);
+        assert_eq!(Fixed::new(1, 0), bodies[0].velocity.x);
+        assert!(bodies[0].is_on_ground);
+
+        // Too high a wall still blocks.
+        let mut bodies = vec![ walker(Fixed::new(1, 0)) ];
+        physics_tick(&config, &mut bodies, &ledge(2));
+        assert_eq!(point3(Fixed::new(2, 128), Fixed::new(1, 0), Fixed::ZERO), bodies[0].position);
+        assert_eq!(Fixed::ZERO, bodies[0].velocity.x);
+
+        // So does a ledge the body can't step onto.
+        let mut bodies = vec![ walker(Fixed::new(0, 128)) ];
+        physics_tick(&config, &mut bodies, &ledge(1));
+        assert_eq!(point3(Fixed::new(2, 128), Fixed::new(1, 0), Fixed::ZERO), bodies[0].position);
+
+        // Nor can a body step up while in the air.
+        let mut bodies = vec![ walker(Fixed::new(1, 0)) ];
+        bodies[0].is_on_ground = false;
+        physics_tick(&config, &mut bodies, &ledge(1));
+        assert_eq!(Fixed::new(2, 128), bodies[0].position.x);
+    }
+
+    #[test]
+    fn max_speed() {
+        let config = PhysicsConfig {
+            gravity: vec3(Fixed::ZERO, -Fixed::new(1, 0), Fixed::ZERO),
+            max_speed: vec3(Fixed::new(0, 128), Fixed::new(2, 0), Fixed::MAX),
+            boundary_policy: BoundaryPolicy::OpenVoid,
+            ..Default::default()
+        };
+        let mut bodies = vec![ PhysicsBody::new() ];
+        let world = one_chunk();
+
+        bodies[0].velocity = vec3(-Fixed::new(3, 0), Fixed::ZERO, Fixed::new(3, 0));
+        let ys = [-1, -3, -5, -7];
+        for &y in ys.iter() {
+            physics_tick(&config, &mut bodies, &world);
+            assert_eq!(vec3(-Fixed::new(0, 128), -Fixed::new(2, 0).min(Fixed::new(-y, 0)), Fixed::new(3, 0)), bodies[0].velocity);
+            assert_eq!(Fixed::new(y, 0), bodies[0].position.y);
+        }
+    }
+
+    #[test]
+    fn budget_exceeded() {
+        let config = PhysicsConfig { max_substeps: 4, boundary_policy: BoundaryPolicy::OpenVoid, ..Default::default() };
+        let mut bodies = vec![ PhysicsBody::new(), PhysicsBody::new() ];
+        let mut world = one_chunk();
+        world.set_voxel(vec3(8, 0, 0), 1);
+
+        for body in bodies.iter_mut() {
+            body.position = point3(Fixed::new(1, 0), Fixed::ZERO, Fixed::ZERO);
+            body.collision_size = vec3(Fixed::new(0, 128), Fixed::new(0, 128), Fixed::new(0, 128));
+        }
+        bodies[0].velocity.x = Fixed::new(20, 0);
+        bodies[1].velocity.x = -Fixed::new(20, 0);
+        bodies[1].position.z = Fixed::new(4, 0);
+
+        // Both bodies stop at the end of the fourth layer past where they started, keeping their velocity.
+        let events = physics_tick(&config, &mut bodies, &world);
+        assert_eq!(vec![PhysicsEvent::BudgetExceeded { body: 0 }, PhysicsEvent::OutOfWorld { body: 1 }, PhysicsEvent::BudgetExceeded { body: 1 }], events);
+        assert_eq!(Fixed::new(5, 255), bodies[0].collision_extent().x);
+        assert_eq!(Fixed::new(20, 0), bodies[0].velocity.x);
+        assert_eq!(-Fixed::new(3, 0), bodies[1].position.x);
+        assert_eq!(-Fixed::new(20, 0), bodies[1].velocity.x);
+
+        // The next tick's move is within budget, and the voxel still blocks.
+        assert_eq!(vec![PhysicsEvent::BudgetExceeded { body: 1 }], physics_tick(&config, &mut bodies, &world));
+        assert_eq!(Fixed::new(7, 128), bodies[0].position.x);
+        assert_eq!(Fixed::ZERO, bodies[0].velocity.x);
+    }
+
+    #[test]
+    fn budget_at_the_edge_of_the_range() {
+        let mut body = PhysicsBody::new();
+        body.collision_size = vec3(Fixed::ONE, Fixed::ONE, Fixed::ONE);
+        for max_substeps in [4, u32::MAX] {
+            let config = PhysicsConfig { max_substeps, ..Default::default() };
+            body.position.x = Fixed::MIN;
+            let (distance, _) = limit_to_budget(&config, &body, 0, Fixed::MAX);
+            assert!(distance >= Fixed::ZERO);
+            body.position.x = Fixed::MAX - Fixed::ONE;
+            let (distance, _) = limit_to_budget(&config, &body, 0, Fixed::MIN);
+            assert!(distance <= Fixed::ZERO);
+        }
+        let config = PhysicsConfig { max_substeps: 4, ..Default::default() };
+        assert_eq!((-Fixed::new(4, 255), true), limit_to_budget(&config, &body, 0, Fixed::MIN));
+    }
+
+    fn cube(position: Point3<Fixed>, collision_mode: CollisionMode) -> PhysicsBody {
+        PhysicsBody {
+            position,
+            collision_size: vec3(Fixed::ONE, Fixed::ONE, Fixed::ONE),
+            collision_mode,
+            ..PhysicsBody::new()
+        }
+    }
+
+    #[test]
+    fn solid_bodies_block() {
+        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, -Fixed::new(0, 128), Fixed::ZERO), ..Default::default() };
+        let mut world = one_chunk();
+        world.set_voxel(vec3(4, 0, 4), 1);
+        let mut bodies = vec![
+            cube(point3(Fixed::new(4, 0), Fixed::new(1, 0), Fixed::new(4, 0)), CollisionMode::Solid),
+            cube(point3(Fixed::new(4, 0), Fixed::new(3, 128), Fixed::new(4, 0)), CollisionMode::Solid),
+        ];
+
+        physics_tick(&config, &mut bodies, &world);
+        assert_eq!(Fixed::new(1, 0), bodies[0].position.y);
+        assert_eq!(Fixed::new(3, 0), bodies[1].position.y);
+        let events = physics_tick(&config, &mut bodies, &world);
+        assert_eq!(Fixed::new(2, 0), bodies[1].position.y);
+        assert_eq!(vec![PhysicsEvent::Contact { bodies: (0, 1), axis: 1, phase: ContactPhase::Started }], events);
+        assert_eq!(vec![Contact { other: 1, axis: 1 }], bodies[0].contacts);
+        assert_eq!(vec![Contact { other: 0, axis: 1 }], bodies[1].contacts);
+        assert!(physics_tick(&config, &mut bodies, &world).is_empty());
+        assert_eq!(Fixed::new(2, 0), bodies[1].position.y);
+        assert!(bodies[1].is_on_ground);
+
+        // Pushing sideways stops at the other body, and slides off once clear of it.
+        bodies[1].velocity.x = -Fixed::new(2, 0);
+        bodies.push(cube(point3(Fixed::new(2, 0), Fixed::new(2, 0), Fixed::new(4, 0)), CollisionMode::Solid));
+        bodies[2].velocity.y = Fixed::new(0, 128);
+        let events = physics_tick(&config, &mut bodies, &world);
+        assert_eq!(Fixed::new(3, 0), bodies[1].position.x);
+        assert_eq!(Fixed::ZERO, bodies[1].velocity.x);
+        assert_eq!(vec![PhysicsEvent::Contact { bodies: (1, 2), axis: 0, phase: ContactPhase::Started }], events);
+    }
+
+    #[test]
+    fn trigger_and_ghost() {
+        let config = PhysicsConfig::default();
+        let world = one_chunk();
+        let mut bodies = vec![
+            cube(point3(Fixed::new(1, 0), Fixed::new(1, 0), Fixed::new(1, 0)), CollisionMode::Solid),
+            cube(point3(Fixed::new(3, 0), Fixed::new(1, 0), Fixed::new(1, 0)), CollisionMode::Trigger),
+            cube(point3(Fixed::new(5, 0), Fixed::new(1, 0), Fixed::new(1, 0)), CollisionMode::Ghost),
+        ];
+        bodies[0].velocity.x = Fixed::new(1, 128);
+
+        // Touching the trigger starts a contact, and moving through it doesn't block.
+        let events = physics_tick(&config, &mut bodies, &world);
+        assert_eq!(vec![PhysicsEvent::Contact { bodies: (0, 1), axis: 0, phase: ContactPhase::Started }], events);
+        assert!(physics_tick(&config, &mut bodies, &world).is_empty());
+        assert_eq!(Fixed::new(4, 0), bodies[0].position.x);
+
+        // Passing through the ghost ends the trigger contact, and nothing else.
+        let events = physics_tick(&config, &mut bodies, &world);
+        assert_eq!(vec![PhysicsEvent::Contact { bodies: (0, 1), axis: 0, phase: ContactPhase::Ended }], events);
+        assert_eq!(Fixed::new(5, 128), bodies[0].position.x);
+        assert!(bodies[0].contacts.is_empty());
+        assert!(bodies[2].contacts.is_empty());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn broadphase_finds_all_contacts(
+            boxes in proptest::collection::vec(((0i32..2048, 0i32..2048, 0i32..2048), (1i32..512, 1i32..512, 1i32..512)), 0..24),
+        ) {
+            let epsilons = |x: i32| Fixed::EPSILON * x;
+            let bodies: Vec<PhysicsBody> = boxes.iter().map(|&(p, size)| PhysicsBody {
+                position: point3(epsilons(p.0), epsilons(p.1), epsilons(p.2)),
+                collision_size: vec3(epsilons(size.0), epsilons(size.1), epsilons(size.2)),
+                ..PhysicsBody::new()
+            }).collect();
+            let pairs = broadphase(&bodies);
+            for a in 0..bodies.len() {
+                for b in a + 1..bodies.len() {
+                    if contact_axis(&bodies[a], &bodies[b]).is_some() {
+                        proptest::prop_assert!(pairs.contains(&(a, b)));
+                    }
+                }
+            }
+        }
+    }
+
+    // The tick as it was before the sweep: one epsilon at a time, checking for overlap each step.
+    fn stepping_tick(config: &PhysicsConfig, body: &mut PhysicsBody, voxels: &VoxelWorld) {
+        let overlaps = |body: &PhysicsBody| {
+            let min = body.position.to_vec().map(physics_to_voxel);
+            let max = body.collision_extent().to_vec().map(physics_to_voxel);
+            body.has_collision() && is_region_solid(config, voxels, min, max)
+        };
+        body.is_on_ground = false;
+        body.velocity = config.add_vec(body.velocity, config.gravity);
+        for axis in 0..3 {
+            let step = if body.velocity[axis].is_negative() { -Fixed::EPSILON } else { Fixed::EPSILON };
+            for _ in 0..body.velocity[axis].epsilons() {
+                let previous = body.position[axis];
+                body.position[axis] = config.add(body.position[axis], step);
+                if overlaps(body) {
+                    body.position[axis] = previous;
+                    body.is_on_ground |= axis == 1 && step.is_negative();
+                    body.velocity[axis] = Fixed::ZERO;
+                    break;
+                }
+            }
+        }
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn sweep_matches_stepping(
+            solid in proptest::collection::vec((0i32..8, 0i32..8, 0i32..8), 0..40),
+            position in (0i32..2048, 0i32..2048, 0i32..2048),
+            size in (1i32..512, 1i32..512, 1i32..512),
+            velocity in (-768i32..768, -768i32..768, -768i32..768),
+            gravity in 0i32..64,
+        ) {
+            let epsilons = |x: i32| Fixed::EPSILON * x;
+            let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, -epsilons(gravity), Fixed::ZERO), ..Default::default() };
+            let mut world = one_chunk();
+            for (x, y, z) in solid {
+                world.set_voxel(vec3(x, y, z), 1);
+            }
+            let mut bodies = vec![ PhysicsBody::new() ];
+            bodies[0].position = point3(epsilons(position.0), epsilons(position.1), epsilons(position.2));
+            bodies[0].collision_size = vec3(epsilons(size.0), epsilons(size.1), epsilons(size.2));
+            bodies[0].velocity = vec3(epsilons(velocity.0), epsilons(velocity.1), epsilons(velocity.2));
+            let mut expected = bodies[0].clone();
+
+            for _ in 0..4 {
+                physics_tick(&config, &mut bodies, &world);
+                stepping_tick(&config, &mut expected, &world);
+                proptest::prop_assert_eq!(expected.position, bodies[0].position);
+                proptest::prop_assert_eq!(expected.velocity, bodies[0].velocity);
+                proptest::prop_assert_eq!(expected.is_on_ground, bodies[0].is_on_ground);
+            }
+        }
+    }
+
+    // Not a correctness test. Run with `cargo test --release physics_tick_benchmark -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn physics_tick_benchmark() {
+        let config = PhysicsConfig { gravity: vec3(Fixed::ZERO, -Fixed::new(0, 16), Fixed::ZERO), ..Default::default() };
+        let mut bodies: Vec<PhysicsBody> = (0..64).map(|_| PhysicsBody::new()).collect();
+        let mut world = one_chunk();
+        for x in 0..CHUNK_SIZE.x as i32 {
+            for z in 0..CHUNK_SIZE.z as i32 {
+                world.set_voxel(vec3(x, 0, z), 1);
+            }
+        }
+
+        const ROUNDS: u32 = 200;
+        const TICKS_PER_ROUND: u32 = 16;
+        let start = std::time::Instant::now();
+        for _ in 0..ROUNDS {
+            // Drop a grid of bodies that slide sideways and land on the floor.
+            for (i, body) in bodies.iter_mut().enumerate() {
+                let (column, row) = ((i % 8) as i32, (i / 8) as i32);
+                body.position = point3(Fixed::new(8 + 2 * column, 0), Fixed::new(6, 0), Fixed::new(8 + 2 * row, 0));
+                let direction = if i % 2 == 0 { Fixed::ONE } else { -Fixed::ONE };
+                body.velocity = vec3(Fixed::new(0, 64), Fixed::ZERO, -Fixed::new(0, 64)) * direction;
+                body.collision_size = vec3(Fixed::new(0, 192), Fixed::new(1, 128), Fixed::new(0, 192));
+            }
+            for _ in 0..TICKS_PER_ROUND {
+                physics_tick(&config, &mut bodies, &world);
+            }
+        }
+        let elapsed = start.elapsed();
+        println!("{} ticks of {} bodies: {:?} ({:?} per tick)", ROUNDS * TICKS_PER_ROUND, bodies.len(), elapsed, elapsed / (ROUNDS * TICKS_PER_ROUND));
+    }
+
+    #[test]
+    #[should_panic]
+    fn panicking_overflow() {
+        let config = PhysicsConfig::default();
+        let mut bodies = vec![ PhysicsBody::new() ];
+        let world = one_chunk();
+
+        bodies[0].position.x = Fixed::MAX - Fixed::EPSILON;
+        bodies[0].velocity.x = Fixed::new(0, 2);
+        physics_tick(&config, &mut bodies, &world);
+    }
 }
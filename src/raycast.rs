@@ -0,0 +1,332 @@
+use cgmath::{EuclideanSpace, Point3, Vector3, point3, vec3};
+
+use crate::ecosim::EcosimEntity;
+use crate::fixed_point::{Fixed, FixedFine};
+use crate::material::material;
+#[cfg(test)]
+use crate::physics_world::PhysicsBody;
+use crate::voxel::VoxelType;
+use crate::voxel_world::VoxelWorld;
+
+/// The box an entity can be picked by, centred on its position in x and z and standing on it in y.
+const ENTITY_PICK_SIZE: Vector3<FixedFine> = vec3(FixedFine::new(1, 0), FixedFine::new(1, 0), FixedFine::new(1, 0));
+
+#[derive(Default)]
+pub struct RaycastOptions<'a> {
+    /// Hits further than this along the ray are ignored. `None` casts to the edge of the world.
+    pub max_distance: Option<FixedFine>,
+    /// Which voxel types the ray stops at. `None` stops at solid materials.
+    pub filter: Option<&'a dyn Fn(VoxelType) -> bool>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct VoxelHit {
+    pub voxel: Vector3<i32>,
+    /// Points out of the face the ray entered through. Zero if the ray started inside the voxel.
+    pub normal: Vector3<i32>,
+    /// Along the ray, from its origin, in voxels.
+    pub distance: FixedFine,
+}
+
+/// A hit against one of the boxes passed to `raycast_bodies` or `raycast_entities`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BoxHit {
+    /// Index into the slice that was cast against.
+    pub index: usize,
+    pub normal: Vector3<i32>,
+    pub distance: FixedFine,
+}
+
+// `None` if the point is too far out for `FixedFine`, in which case it's out of reach of any ray.
+fn to_fine(point: Point3<Fixed>) -> Option<Point3<FixedFine>> {
+    Some(point3(point.x.checked_convert()?, point.y.checked_convert()?, point.z.checked_convert()?))
+}
+
+// Like `checked_div`, but results too large to represent saturate, since they're only compared.
+fn ray_div(lhs: FixedFine, rhs: FixedFine) -> FixedFine {
+    lhs.checked_div(rhs).unwrap_or(if lhs.is_negative() == rhs.is_negative() { FixedFine::MAX } else { FixedFine::MIN })
+}
+
+/// Walks the voxels a ray passes through, in order, and returns the first one the filter accepts.
+/// Positions are in physics units, so a voxel is one unit across. The direction doesn't need to be
+/// normalised, but a zero direction never hits anything.
+#[cfg(test)]
+pub fn raycast_voxels(voxels: &VoxelWorld, origin: Point3<FixedFine>, direction: Vector3<FixedFine>, options: &RaycastOptions) -> Option<VoxelHit> {
+    raycast_voxels_from(voxels, vec3(0, 0, 0), origin, direction, options)
+}
+
+// Casts with `origin` relative to the voxel `base`, so rays far from the world origin don't need
+// large fixed-point values.
+fn raycast_voxels_from(voxels: &VoxelWorld, base: Vector3<i32>, origin: Point3<FixedFine>, direction: Vector3<FixedFine>, options: &RaycastOptions) -> Option<VoxelHit> {
+    let direction = FixedFine::vector3_normalize(direction);
+    if direction == FixedFine::ZERO_VECTOR {
+        return None;
+    }
+    let (world_min, world_max) = voxels.voxel_bounds()?;
+    let is_hit = |voxel: Vector3<i32>| {
+        let Some(value) = voxels.get_voxel(voxel) else {
+            return false;
+        };
+        match options.filter {
+            Some(filter) => filter(value),
+            None => material(value).is_solid,
+        }
+    };
+    let max_distance = options.max_distance.unwrap_or(FixedFine::MAX);
+    let mut voxel = vec3(0, 0, 0);
+    let mut step = vec3(0, 0, 0);
+    // How far along the ray the next boundary on each axis is, and how far apart boundaries are.
+    let mut next = vec3(FixedFine::MAX, FixedFine::MAX, FixedFine::MAX);
+    let mut delta = vec3(FixedFine::MAX, FixedFine::MAX, FixedFine::MAX);
+    for axis in 0..3 {
+        let (o, d) = (origin[axis], direction[axis]);
+        if d.is_negative() {
+            // A ray starting on a boundary and moving down starts in the lower voxel.
+            voxel[axis] = o.ceil_i32() - 1;
+            step[axis] = -1;
+            next[axis] = ray_div(o - FixedFine::new(voxel[axis], 0), -d);
+            delta[axis] = ray_div(FixedFine::ONE, -d);
+        } else {
+            voxel[axis] = o.floor_i32();
+            if d != FixedFine::ZERO {
+                step[axis] = 1;
+                next[axis] = ray_div(FixedFine::new(voxel[axis] + 1, 0) - o, d);
+                delta[axis] = ray_div(FixedFine::ONE, d);
+            }
+        }
+        voxel[axis] = base[axis].checked_add(voxel[axis])?;
+    }
+    let mut distance = FixedFine::ZERO;
+    let mut normal = vec3(0, 0, 0);
+    loop {
+        if is_hit(voxel) {
+            return Some(VoxelHit { voxel, normal, distance });
+        }
+        // Give up once the ray can't come back into the world.
+        let leaving = (0..3).any(|axis| {
+            (voxel[axis] < world_min[axis] && step[axis] <= 0) || (voxel[axis] >= world_max[axis] && step[axis] >= 0)
+        });
+        if leaving {
+            return None;
+        }
+        let axis = (0..3).min_by_key(|&axis| next[axis]).unwrap();
+        distance = next[axis];
+        if distance > max_distance || distance == FixedFine::MAX {
+            return None;
+        }
+        voxel[axis] += step[axis];
+        normal = vec3(0, 0, 0);
+        normal[axis] = -step[axis];
+        next[axis] = next[axis].saturating_add(delta[axis]);
+    }
+}
+
+/// `raycast_voxels` for callers working in floats, such as the camera. Positions are still in
+/// physics units. Returns `None` for origins and directions too large to cast from.
+pub fn raycast_voxels_f32(voxels: &VoxelWorld, origin: Point3<f32>, direction: Vector3<f32>, options: &RaycastOptions) -> Option<VoxelHit> {
+    let base = origin.map(f32::floor);
+    // This also catches NaN.
+    if !(0..3).all(|axis| base[axis].abs() < i32::MAX as f32) {
+        return None;
+    }
+    let fine = |v: Vector3<f32>| Some(vec3(FixedFine::checked_from_f32(v.x)?, FixedFine::checked_from_f32(v.y)?, FixedFine::checked_from_f32(v.z)?));
+    let offset = fine(origin - base)?;
+    raycast_voxels_from(voxels, base.map(|c| c as i32).to_vec(), Point3::from_vec(offset), fine(direction)?, options)
+}
+
+// Slab test against each box, keeping the nearest hit.
+fn raycast_boxes(boxes: impl Iterator<Item = (usize, Point3<FixedFine>, Point3<FixedFine>)>, origin: Point3<FixedFine>, direction: Vector3<FixedFine>, max_distance: Option<FixedFine>) -> Option<BoxHit> {
+    let direction = FixedFine::vector3_normalize(direction);
+    if direction == FixedFine::ZERO_VECTOR {
+        return None;
+    }
+    let mut nearest: Option<BoxHit> = None;
+    'boxes: for (index, min, max) in boxes {
+        // The ray is inside the box between `enter` and `exit`.
+        let (mut enter, mut exit) = (FixedFine::MIN, FixedFine::MAX);
+        let mut normal = vec3(0, 0, 0);
+        for axis in 0..3 {
+            let (o, d) = (origin[axis], direction[axis]);
+            if d == FixedFine::ZERO {
+                if o < min[axis] || o > max[axis] {
+                    continue 'boxes;
+                }
+                continue;
+            }
+            let (near, far) = if d.is_negative() {
+                (ray_div(max[axis] - o, d), ray_div(min[axis] - o, d))
+            } else {
+                (ray_div(min[axis] - o, d), ray_div(max[axis] - o, d))
+            };
+            if near > enter {
+                enter = near;
+                normal = vec3(0, 0, 0);
+                normal[axis] = if d.is_negative() { 1 } else { -1 };
+            }
+            exit = exit.min(far);
+        }
+        if enter > exit || exit.is_negative() || max_distance.is_some_and(|max_distance| enter > max_distance) {
+            continue;
+        }
+        if enter.is_negative() {
+            // Started inside the box.
+            (enter, normal) = (FixedFine::ZERO, vec3(0, 0, 0));
+        }
+        if nearest.is_none_or(|hit| enter < hit.distance) {
+            nearest = Some(BoxHit { index, normal, distance: enter });
+        }
+    }
+    nearest
+}
+
+/// Casts against the collision boxes of bodies that have one.
+#[cfg(test)]
+pub fn raycast_bodies(bodies: &[PhysicsBody], origin: Point3<FixedFine>, direction: Vector3<FixedFine>, max_distance: Option<FixedFine>) -> Option<BoxHit> {
+    let boxes = bodies.iter().enumerate()
+        .filter(|(_, body)| body.has_collision())
+        .filter_map(|(index, body)| Some((index, to_fine(body.position)?, to_fine(body.position + body.collision_size)?)));
+    raycast_boxes(boxes, origin, direction, max_distance)
+}
+
+/// Casts against a small box around each entity, since entities are only points.
+pub fn raycast_entities(entities: &[EcosimEntity], origin: Point3<FixedFine>, direction: Vector3<FixedFine>, max_distance: Option<FixedFine>) -> Option<BoxHit> {
+    let half = vec3(ENTITY_PICK_SIZE.x / FixedFine::new(2, 0), FixedFine::ZERO, ENTITY_PICK_SIZE.z / FixedFine::new(2, 0));
+    let boxes = entities.iter().enumerate().filter_map(|(index, entity)| {
+        let min = to_fine(entity.position)? - half;
+        Some((index, min, min + ENTITY_PICK_SIZE))
+    });
+    raycast_boxes(boxes, origin, direction, max_distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cgmath::point3;
+
+    fn fine(whole: i32, fraction: u32) -> FixedFine {
+        FixedFine::new(whole, fraction)
+    }
+
+    fn floor() -> VoxelWorld {
+        let mut world = VoxelWorld::new();
+        for i in 0..8 {
+            for k in 0..8 {
+                world.set_voxel(vec3(i, 0, k), 1);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn straight_down() {
+        let world = floor();
+        let hit = raycast_voxels(&world, point3(fine(2, 32768), fine(5, 0), fine(3, 32768)), vec3(FixedFine::ZERO, -fine(3, 0), FixedFine::ZERO), &RaycastOptions::default());
+        assert_eq!(Some(VoxelHit { voxel: vec3(2, 0, 3), normal: vec3(0, 1, 0), distance: fine(4, 0) }), hit);
+
+        let options = RaycastOptions { max_distance: Some(fine(3, 0)), ..Default::default() };
+        assert_eq!(None, raycast_voxels(&world, point3(fine(2, 32768), fine(5, 0), fine(3, 32768)), vec3(FixedFine::ZERO, -FixedFine::ONE, FixedFine::ZERO), &options));
+
+        // Leaving the chunk ends the ray.
+        assert_eq!(None, raycast_voxels(&world, point3(fine(2, 32768), fine(5, 0), fine(3, 32768)), vec3(FixedFine::ZERO, FixedFine::ONE, FixedFine::ZERO), &RaycastOptions::default()));
+    }
+
+    #[test]
+    fn diagonal() {
+        let mut world = VoxelWorld::new();
+        world.set_voxel(vec3(3, 1, 1), 1);
+        world.set_voxel(vec3(4, 2, 1), 1);
+
+        // The ray rises into the second row before it reaches the fourth column, so it enters (3, 1) from the side.
+        let hit = raycast_voxels(&world, point3(fine(0, 32768), fine(0, 0), fine(1, 32768)), vec3(FixedFine::ONE, fine(0, 32768), FixedFine::ZERO), &RaycastOptions::default()).unwrap();
+        assert_eq!(vec3(3, 1, 1), hit.voxel);
+        assert_eq!(vec3(-1, 0, 0), hit.normal);
+    }
+
+    #[test]
+    fn starts_inside() {
+        let world = floor();
+        let hit = raycast_voxels_f32(&world, point3(1.5, 0.5, 1.5), vec3(1.0, 0.0, 0.0), &RaycastOptions::default());
+        assert_eq!(Some(VoxelHit { voxel: vec3(1, 0, 1), normal: vec3(0, 0, 0), distance: FixedFine::ZERO }), hit);
+    }
+
+    #[test]
+    fn from_outside_the_chunk() {
+        let world = floor();
+        let hit = raycast_voxels_f32(&world, point3(-3.5, 0.5, 1.5), vec3(1.0, 0.0, 0.0), &RaycastOptions::default());
+        assert_eq!(Some(VoxelHit { voxel: vec3(0, 0, 1), normal: vec3(-1, 0, 0), distance: fine(3, 32768) }), hit);
+    }
+
+    #[test]
+    fn across_chunks() {
+        let mut world = floor();
+        world.set_voxel(vec3(-40, 0, 1), 1);
+        let hit = raycast_voxels_f32(&world, point3(-0.5, 0.5, 1.5), vec3(-1.0, 0.0, 0.0), &RaycastOptions::default());
+        assert_eq!(Some(VoxelHit { voxel: vec3(-40, 0, 1), normal: vec3(1, 0, 0), distance: fine(38, 32768) }), hit);
+        // There's nothing loaded past x = -64.
+        assert_eq!(None, raycast_voxels_f32(&world, point3(-41.5, 0.5, 1.5), vec3(-1.0, 0.0, 0.0), &RaycastOptions::default()));
+    }
+
+    #[test]
+    fn far_from_the_origin() {
+        let mut world = VoxelWorld::new();
+        world.set_voxel(vec3(100_000, 0, -100_000), 1);
+        let hit = raycast_voxels_f32(&world, point3(99_990.5, 0.5, -99_999.5), vec3(1.0, 0.0, 0.0), &RaycastOptions::default());
+        assert_eq!(Some(VoxelHit { voxel: vec3(100_000, 0, -100_000), normal: vec3(-1, 0, 0), distance: fine(9, 32768) }), hit);
+    }
+
+    #[test]
+    fn f32_rounding_and_overflow() {
+        let world = floor();
+        // Both round up to a whole voxel in `FixedFine`.
+        let hit = raycast_voxels_f32(&world, point3(2.5, 10.999997, 2.5), vec3(0.0, -0.999_999_8, 0.0), &RaycastOptions::default());
+        assert_eq!(vec3(2, 0, 2), hit.unwrap().voxel);
+        assert_eq!(None, raycast_voxels_f32(&world, point3(f32::NAN, 0.5, 0.5), vec3(1.0, 0.0, 0.0), &RaycastOptions::default()));
+        assert_eq!(None, raycast_voxels_f32(&world, point3(3e9, 0.5, 0.5), vec3(1.0, 0.0, 0.0), &RaycastOptions::default()));
+        assert_eq!(None, raycast_voxels_f32(&world, point3(0.5, 0.5, 0.5), vec3(1e6, 0.0, 0.0), &RaycastOptions::default()));
+    }
+
+    #[test]
+    fn filter() {
+        let mut world = floor();
+        world.set_voxel(vec3(2, 3, 2), 2);
+        let only_two = |voxel: VoxelType| voxel == 2;
+        let options = RaycastOptions { filter: Some(&only_two), ..Default::default() };
+
+        let origin = point3(2.5, 6.0, 2.5);
+        let down = vec3(0.0, -1.0, 0.0);
+        assert_eq!(vec3(2, 3, 2), raycast_voxels_f32(&world, origin, down, &options).unwrap().voxel);
+        world.set_voxel(vec3(2, 3, 2), 0);
+        assert_eq!(None, raycast_voxels_f32(&world, origin, down, &options));
+        assert_eq!(vec3(2, 0, 2), raycast_voxels_f32(&world, origin, down, &RaycastOptions::default()).unwrap().voxel);
+    }
+
+    #[test]
+    fn bodies() {
+        let mut bodies = vec![ PhysicsBody::new(), PhysicsBody::new(), PhysicsBody::new() ];
+        for (body, x) in bodies.iter_mut().zip([6, 3, 9]) {
+            body.position = point3(Fixed::new(x, 0), Fixed::ZERO, Fixed::ZERO);
+            body.collision_size = vec3(Fixed::ONE, Fixed::ONE, Fixed::ONE);
+        }
+        bodies[1].collision_size = Fixed::ZERO_VECTOR;
+        let origin = point3(FixedFine::ZERO, fine(0, 32768), fine(0, 32768));
+        let right = vec3(FixedFine::ONE, FixedFine::ZERO, FixedFine::ZERO);
+
+        // The body without a collision size is skipped.
+        assert_eq!(Some(BoxHit { index: 0, normal: vec3(-1, 0, 0), distance: fine(6, 0) }), raycast_bodies(&bodies, origin, right, None));
+        assert_eq!(None, raycast_bodies(&bodies, origin, right, Some(fine(5, 0))));
+        assert_eq!(None, raycast_bodies(&bodies, origin, -right, None));
+        let inside = point3(fine(6, 32768), fine(0, 32768), fine(0, 32768));
+        assert_eq!(Some(BoxHit { index: 0, normal: vec3(0, 0, 0), distance: FixedFine::ZERO }), raycast_bodies(&bodies, inside, right, None));
+    }
+
+    #[test]
+    fn entities() {
+        let entities = vec![ EcosimEntity::new(vec3(4, 1, 4)), EcosimEntity::new(vec3(4, 1, 8)) ];
+        let origin = to_fine(entities[1].position).unwrap() + vec3(FixedFine::ZERO, fine(4, 0), FixedFine::ZERO);
+        let hit = raycast_entities(&entities, origin, vec3(FixedFine::ZERO, -FixedFine::ONE, FixedFine::ZERO), None).unwrap();
+        assert_eq!(1, hit.index);
+        assert_eq!(vec3(0, 1, 0), hit.normal);
+        assert_eq!(fine(3, 0), hit.distance);
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use std::ops::Range;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -7,10 +8,57 @@ pub struct Vertex {
     pub light: [f32; 3],
     pub normal: [f32; 3],
     pub uv: [f32; 2],
+    pub color: [f32; 3],
+    /// The tile `uv` repeats within, as min u, min v, max u, max v.
+    pub atlas_rect: [f32; 4],
 }
 
 impl Vertex {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3, 3 => Float32x2];
+    const ATTRIBUTES: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3, 3 => Float32x2, 4 => Float32x3, 5 => Float32x4];
+
+    pub fn buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// For outlines, drawn as a line list.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: [f32; 3],
+    pub color: [f32; 3],
+}
+
+impl LineVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    pub fn buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// For screen-space overlays such as the crosshair. Positions are in normalized device coordinates.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OverlayVertex {
+    pub position: [f32; 2],
+    pub uv: [f32; 2],
+}
+
+impl OverlayVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
 
     pub fn buffer_layout() -> wgpu::VertexBufferLayout<'static> {
         use std::mem;
@@ -47,3 +95,190 @@ impl MovingAverage {
         self.samples.iter().sum::<f64>() / self.samples.len() as f64
     }
 }
+
+// Makes `buffer` at least `size` bytes, replacing it with a bigger one if it isn't. The contents
+// aren't kept.
+fn reserve_buffer(device: &wgpu::Device, buffer: &mut wgpu::Buffer, size: u64, label: &str) {
+    if buffer.size() < size {
+        *buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: size.next_power_of_two(),
+            usage: buffer.usage(),
+            mapped_at_creation: false,
+        });
+    }
+}
+
+/// An indexed mesh kept on the GPU between frames. It's only uploaded again when its generation
+/// changes, into the same buffers if it still fits.
+pub struct MeshBuffers {
+    label: &'static str,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    n_indices: u32,
+    generation: Option<u64>,
+}
+
+impl MeshBuffers {
+    pub fn new(device: &wgpu::Device, label: &'static str) -> Self {
+        let create = |usage| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: 1 << 16,
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        MeshBuffers {
+            label,
+            vertex_buffer: create(wgpu::BufferUsages::VERTEX),
+            index_buffer: create(wgpu::BufferUsages::INDEX),
+            n_indices: 0,
+            generation: None,
+        }
+    }
+
+    pub fn update(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, generation: u64, vertices: &[Vertex], indices: &[u32]) {
+        if self.generation == Some(generation) {
+            return;
+        }
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(vertices);
+        let index_bytes: &[u8] = bytemuck::cast_slice(indices);
+        reserve_buffer(device, &mut self.vertex_buffer, vertex_bytes.len() as u64, self.label);
+        reserve_buffer(device, &mut self.index_buffer, index_bytes.len() as u64, self.label);
+        queue.write_buffer(&self.vertex_buffer, 0, vertex_bytes);
+        queue.write_buffer(&self.index_buffer, 0, index_bytes);
+        self.n_indices = indices.len() as u32;
+        self.generation = Some(generation);
+    }
+
+    /// Expects the pipeline and bind groups to be set already.
+    pub fn draw(&self, render_pass: &mut wgpu::RenderPass) {
+        if self.n_indices == 0 {
+            return;
+        }
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.n_indices, 0, 0..1);
+    }
+}
+
+/// Hands out space in a buffer that's written again every frame, going round from the end back to
+/// the start instead of allocating anything new.
+pub struct RingAllocator {
+    capacity: u64,
+    head: u64,
+}
+
+impl RingAllocator {
+    pub fn new(capacity: u64) -> Self {
+        RingAllocator { capacity, head: 0 }
+    }
+
+    /// Where to put `size` bytes, or None if they wouldn't fit even with the whole ring free.
+    /// Offsets are aligned for `Queue::write_buffer`.
+    pub fn allocate(&mut self, size: u64) -> Option<u64> {
+        let size = size.next_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT);
+        if size > self.capacity {
+            return None;
+        }
+        if self.head + size > self.capacity {
+            self.head = 0;
+        }
+        let offset = self.head;
+        self.head += size;
+        Some(offset)
+    }
+}
+
+/// For geometry that changes every frame, like the player model and flowers. Vertices are staged
+/// on the CPU during the frame, then written to the ring buffer in one upload.
+pub struct DynamicVertexBuffer {
+    buffer: wgpu::Buffer,
+    allocator: RingAllocator,
+    staging: Vec<u8>,
+    // Where this frame's staging data went in `buffer`.
+    frame_offset: u64,
+}
+
+impl DynamicVertexBuffer {
+    pub fn new(device: &wgpu::Device, capacity: u64) -> Self {
+        DynamicVertexBuffer {
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Dynamic Vertex Buffer"),
+                size: capacity,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            allocator: RingAllocator::new(capacity),
+            staging: vec![],
+            frame_offset: 0,
+        }
+    }
+
+    /// Returns the range to pass to `slice` once the frame's been uploaded.
+    pub fn stage<T: bytemuck::Pod>(&mut self, vertices: &[T]) -> Range<u64> {
+        let start = self.staging.len() as u64;
+        self.staging.extend_from_slice(bytemuck::cast_slice(vertices));
+        start..self.staging.len() as u64
+    }
+
+    /// Writes everything staged since the last upload to the GPU.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let size = self.staging.len() as u64;
+        let offset = match self.allocator.allocate(size) {
+            Some(offset) => offset,
+            None => {
+                // Grow so a few frames fit before wrapping round.
+                reserve_buffer(device, &mut self.buffer, size * 4, "Dynamic Vertex Buffer");
+                self.allocator = RingAllocator::new(self.buffer.size());
+                self.allocator.allocate(size).unwrap()
+            }
+        };
+        if size > 0 {
+            queue.write_buffer(&self.buffer, offset, &self.staging);
+        }
+        self.staging.clear();
+        self.frame_offset = offset;
+    }
+
+    /// Panics if the range is empty.
+    pub fn slice(&self, range: Range<u64>) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(self.frame_offset + range.start..self.frame_offset + range.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_allocates_in_order() {
+        let mut ring = RingAllocator::new(100);
+        assert_eq!(Some(0), ring.allocate(40));
+        assert_eq!(Some(40), ring.allocate(40));
+    }
+
+    #[test]
+    fn ring_wraps_when_full() {
+        let mut ring = RingAllocator::new(100);
+        ring.allocate(40);
+        ring.allocate(40);
+        // 80 + 40 goes past the end, so it starts again from the beginning.
+        assert_eq!(Some(0), ring.allocate(40));
+        assert_eq!(Some(40), ring.allocate(60));
+        assert_eq!(Some(0), ring.allocate(1));
+    }
+
+    #[test]
+    fn ring_aligns_offsets() {
+        let mut ring = RingAllocator::new(100);
+        ring.allocate(3);
+        assert_eq!(Some(wgpu::COPY_BUFFER_ALIGNMENT), ring.allocate(3));
+    }
+
+    #[test]
+    fn ring_rejects_oversized() {
+        let mut ring = RingAllocator::new(100);
+        assert_eq!(None, ring.allocate(101));
+        assert_eq!(Some(0), ring.allocate(100));
+    }
+}
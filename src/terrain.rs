@@ -0,0 +1,368 @@
+use cgmath::{Point3, point3, Vector3, vec3};
+
+use crate::fixed_point::Fixed;
+use crate::material::{AIR, DIRT, SAND, STONE, WATER, material};
+use crate::voxel::{CHUNK_SIZE, VoxelChunk, VoxelType};
+use crate::voxel_world::chunk_origin;
+
+/// A flower a chunk starts with.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FlowerSeed {
+    pub position: Point3<Fixed>,
+    pub genome: u32,
+}
+
+pub struct GeneratedChunk {
+    pub chunk: VoxelChunk,
+    pub flowers: Vec<FlowerSeed>,
+}
+
+/// Makes the world's chunks the first time they're loaded. Runs on worker threads, and has to
+/// give the same chunk every time it's asked for the same coordinate.
+pub trait TerrainGenerator: Send + Sync {
+    fn generate(&self, chunk_coord: Vector3<i32>) -> GeneratedChunk;
+
+    /// The lowest y in a column that's above the ground and any water on it, for the player to
+    /// spawn at.
+    fn spawn_height(&self, x: i32, z: i32) -> i32;
+}
+
+// Noise is computed in f64 with only arithmetic and `floor`, which give the same results on every
+// platform, so a seed always makes the same world.
+
+fn hash(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+    let mut h = seed ^ 0x9e37_79b9_7f4a_7c15;
+    for v in [x, y, z] {
+        h = (h ^ v as u32 as u64).wrapping_mul(0xff51_afd7_ed55_8ccd);
+        h ^= h >> 33;
+    }
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+/// From 0 to 1, exclusive.
+fn hash_unit(seed: u64, x: i32, y: i32, z: i32) -> f64 {
+    (hash(seed, x, y, z) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Smoothly interpolated random values on an integer lattice, from -1 to 1.
+fn value_noise(seed: u64, p: Vector3<f64>) -> f64 {
+    let cell = p.map(|c| c.floor());
+    let t = (p - cell).map(|f| f * f * (3.0 - 2.0 * f));
+    let [x, y, z] = [cell.x as i32, cell.y as i32, cell.z as i32];
+    let corner = |dx, dy, dz| hash_unit(seed, x + dx, y + dy, z + dz) * 2.0 - 1.0;
+    let plane = |dz| lerp(
+        lerp(corner(0, 0, dz), corner(1, 0, dz), t.x),
+        lerp(corner(0, 1, dz), corner(1, 1, dz), t.x),
+        t.y,
+    );
+    lerp(plane(0), plane(1), t.z)
+}
+
+/// Octaves of value noise, each twice the frequency and half the amplitude of the last. From -1
+/// to 1.
+fn fractal_noise(seed: u64, p: Vector3<f64>, frequency: f64, octaves: u32) -> f64 {
+    let (mut sum, mut total_amplitude, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, frequency);
+    for octave in 0..octaves {
+        sum += value_noise(seed.wrapping_add(octave as u64), p * frequency) * amplitude;
+        total_amplitude += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / total_amplitude
+}
+
+fn fractal_noise_2d(seed: u64, x: i32, z: i32, frequency: f64, octaves: u32) -> f64 {
+    fractal_noise(seed, vec3(x as f64, 0.0, z as f64), frequency, octaves)
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Biome {
+    Meadow,
+    Heath,
+    Desert,
+    Mountains,
+}
+
+impl Biome {
+    /// The top voxel of a column that's above water.
+    pub fn surface(self) -> VoxelType {
+        match self {
+            Biome::Meadow | Biome::Heath => DIRT,
+            Biome::Desert => SAND,
+            Biome::Mountains => STONE,
+        }
+    }
+
+    // How many voxels of the surface material there are before stone.
+    fn soil_depth(self) -> i32 {
+        match self {
+            Biome::Meadow | Biome::Heath => 3,
+            Biome::Desert => 5,
+            Biome::Mountains => 1,
+        }
+    }
+
+    // The chance of a flower on each voxel of surface.
+    fn flower_density(self) -> f64 {
+        match self {
+            Biome::Meadow => 1.0 / 12.0,
+            Biome::Heath => 1.0 / 40.0,
+            Biome::Desert | Biome::Mountains => 0.0,
+        }
+    }
+
+    // The genome bits that set a flower's colours. Flowers in each biome start out the same colour,
+    // and mutate away from it.
+    fn flower_colors(self) -> u32 {
+        match self {
+            Biome::Meadow => 0b11,
+            Biome::Heath => 0b00,
+            Biome::Desert | Biome::Mountains => 0b01,
+        }
+    }
+}
+
+const FLOWER_COLOR_MASK: u32 = 0b11;
+
+#[derive(Copy, Clone, Debug)]
+struct Column {
+    /// The y of the highest ground voxel.
+    height: i32,
+    biome: Biome,
+}
+
+/// Rolling fractal-noise terrain, with mountains, lakes, and caves.
+pub struct NoiseTerrain {
+    seed: u64,
+}
+
+impl NoiseTerrain {
+    const BASE_HEIGHT: i32 = 8;
+    const SEA_LEVEL: i32 = 4;
+    // Caves stay this far under the surface, so they don't leave holes in it, and don't go below
+    // the floor, so there's always ground above where fallen players respawn.
+    const CAVE_ROOF_DEPTH: i32 = 4;
+    const CAVE_FLOOR: i32 = -48;
+
+    // Each noise field gets its own seed, so they aren't the same shape.
+    const HEIGHT_SEED: u64 = 0;
+    const RUGGEDNESS_SEED: u64 = 100;
+    const MOISTURE_SEED: u64 = 200;
+    const CAVE_SEED: u64 = 300;
+    const FLOWER_SEED: u64 = 400;
+
+    pub fn new(seed: u64) -> Self {
+        NoiseTerrain { seed }
+    }
+
+    fn seed(&self, field: u64) -> u64 {
+        hash(self.seed, field as i32, 0, 0)
+    }
+
+    fn column(&self, x: i32, z: i32) -> Column {
+        // Ruggedness scales the height, rather than picking between heights, so biomes meet
+        // without cliffs.
+        let ruggedness = (fractal_noise_2d(self.seed(Self::RUGGEDNESS_SEED), x, z, 1.0 / 512.0, 3) * 0.5 + 0.5).clamp(0.0, 1.0);
+        let amplitude = 6.0 + 48.0 * ruggedness * ruggedness;
+        let height = Self::BASE_HEIGHT + (fractal_noise_2d(self.seed(Self::HEIGHT_SEED), x, z, 1.0 / 128.0, 5) * amplitude).floor() as i32;
+        let moisture = fractal_noise_2d(self.seed(Self::MOISTURE_SEED), x, z, 1.0 / 256.0, 3);
+        let biome = if ruggedness > 0.65 {
+            Biome::Mountains
+        } else if moisture < -0.2 {
+            Biome::Desert
+        } else if moisture > 0.15 {
+            Biome::Meadow
+        } else {
+            Biome::Heath
+        };
+        Column { height, biome }
+    }
+
+    fn is_cave(&self, coord: Vector3<i32>) -> bool {
+        // Caves follow where the noise crosses zero, so they're long winding tunnels.
+        let p = vec3(coord.x as f64, coord.y as f64 * 1.5, coord.z as f64);
+        fractal_noise(self.seed(Self::CAVE_SEED), p, 1.0 / 48.0, 2).abs() < 0.06
+    }
+
+    fn voxel(&self, column: Column, coord: Vector3<i32>) -> VoxelType {
+        if coord.y > column.height {
+            return if coord.y <= Self::SEA_LEVEL { WATER } else { AIR };
+        }
+        if coord.y < column.height - Self::CAVE_ROOF_DEPTH && coord.y > Self::CAVE_FLOOR && self.is_cave(coord) {
+            return AIR;
+        }
+        if coord.y <= column.height - column.biome.soil_depth() {
+            STONE
+        } else if column.height < Self::SEA_LEVEL {
+            // Lake beds.
+            SAND
+        } else {
+            column.biome.surface()
+        }
+    }
+
+    fn flower(&self, column: Column, x: i32, z: i32) -> Option<FlowerSeed> {
+        let biome = column.biome;
+        if column.height < Self::SEA_LEVEL || !material(biome.surface()).supports_flowers {
+            return None;
+        }
+        let seed = self.seed(Self::FLOWER_SEED);
+        if hash_unit(seed, x, 0, z) >= biome.flower_density() {
+            return None;
+        }
+        let bits = hash(seed, x, 1, z);
+        let genome = (bits as u32 & !FLOWER_COLOR_MASK) | biome.flower_colors();
+        // Somewhere inside the voxel, like flowers that grow. `Fixed::new` takes the fraction away
+        // from negative numbers, so it's added separately.
+        let offset = |shift: u32| Fixed::new(0, 16 + ((bits >> (32 + shift)) & 0xff) as u32 * 224 / 255);
+        Some(FlowerSeed {
+            position: point3(Fixed::new(x, 0) + offset(0), Fixed::new(column.height + 1, 0), Fixed::new(z, 0) + offset(8)),
+            genome,
+        })
+    }
+}
+
+impl TerrainGenerator for NoiseTerrain {
+    fn generate(&self, chunk_coord: Vector3<i32>) -> GeneratedChunk {
+        let origin = chunk_origin(chunk_coord);
+        let mut chunk = VoxelChunk::new();
+        let mut flowers = vec![];
+        for i in 0..CHUNK_SIZE.x {
+            for k in 0..CHUNK_SIZE.z {
+                let (x, z) = (origin.x + i as i32, origin.z + k as i32);
+                let column = self.column(x, z);
+                // Columns entirely above the ground have nothing in them.
+                if origin.y > column.height.max(Self::SEA_LEVEL) {
+                    continue;
+                }
+                for j in 0..CHUNK_SIZE.y {
+                    let voxel = self.voxel(column, vec3(x, origin.y + j as i32, z));
+                    if voxel != AIR {
+                        chunk.set_voxel(vec3(i, j, k), voxel);
+                    }
+                }
+                // Flowers belong to the chunk they stand in.
+                let flower_y = column.height + 1 - origin.y;
+                if (0..CHUNK_SIZE.y as i32).contains(&flower_y) && let Some(flower) = self.flower(column, x, z) {
+                    flowers.push(flower);
+                }
+            }
+        }
+        GeneratedChunk { chunk, flowers }
+    }
+
+    fn spawn_height(&self, x: i32, z: i32) -> i32 {
+        self.column(x, z).height.max(Self::SEA_LEVEL) + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn voxels(chunk: &VoxelChunk) -> Vec<VoxelType> {
+        let mut voxels = vec![];
+        for i in 0..CHUNK_SIZE.x {
+            for j in 0..CHUNK_SIZE.y {
+                for k in 0..CHUNK_SIZE.z {
+                    voxels.push(chunk.get_voxel(vec3(i, j, k)));
+                }
+            }
+        }
+        voxels
+    }
+
+    #[test]
+    fn noise_range() {
+        for i in 0..1000 {
+            let p = vec3(i as f64 * 0.37, i as f64 * -1.13, i as f64 * 2.71);
+            let value = fractal_noise(7, p, 0.5, 4);
+            assert!((-1.0..=1.0).contains(&value), "{}", value);
+        }
+        // Continuous, so terrain doesn't have steps at lattice points.
+        let a = value_noise(7, vec3(2.0 - 1e-9, 0.5, 0.5));
+        let b = value_noise(7, vec3(2.0, 0.5, 0.5));
+        assert!((a - b).abs() < 1e-6);
+    }
+
+    #[test]
+    fn deterministic_from_seed() {
+        for chunk_coord in [vec3(0, 0, 0), vec3(-3, -1, 5)] {
+            let a = NoiseTerrain::new(42).generate(chunk_coord);
+            let b = NoiseTerrain::new(42).generate(chunk_coord);
+            assert!(voxels(&a.chunk) == voxels(&b.chunk));
+            assert_eq!(a.flowers, b.flowers);
+        }
+        let other = NoiseTerrain::new(43).generate(vec3(0, 0, 0));
+        assert!(voxels(&NoiseTerrain::new(42).generate(vec3(0, 0, 0)).chunk) != voxels(&other.chunk));
+    }
+
+    #[test]
+    fn biomes_pick_surface_and_flowers() {
+        let terrain = NoiseTerrain::new(1);
+        let mut biomes = vec![];
+        for x in (-2048..2048).step_by(37) {
+            for z in (-2048..2048).step_by(41) {
+                let column = terrain.column(x, z);
+                biomes.push(column.biome);
+                if column.height >= NoiseTerrain::SEA_LEVEL {
+                    assert_eq!(column.biome.surface(), terrain.voxel(column, vec3(x, column.height, z)));
+                    assert_eq!(AIR, terrain.voxel(column, vec3(x, column.height + 1, z)));
+                }
+                if let Some(flower) = terrain.flower(column, x, z) {
+                    assert!(material(terrain.voxel(column, vec3(x, column.height, z))).supports_flowers);
+                    assert_eq!(column.biome.flower_colors(), flower.genome & FLOWER_COLOR_MASK);
+                }
+            }
+        }
+        for biome in [Biome::Meadow, Biome::Heath, Biome::Desert, Biome::Mountains] {
+            assert!(biomes.contains(&biome), "{:?}", biome);
+        }
+    }
+
+    #[test]
+    fn flowers_stand_in_their_chunk() {
+        let terrain = NoiseTerrain::new(5);
+        let mut n_flowers = 0;
+        for x in -2..2 {
+            for z in -2..2 {
+                for y in -1..1 {
+                    let chunk_coord = vec3(x, y, z);
+                    let origin = chunk_origin(chunk_coord);
+                    let generated = terrain.generate(chunk_coord);
+                    for flower in &generated.flowers {
+                        let voxel = vec3(flower.position.x.floor_i32(), flower.position.y.floor_i32(), flower.position.z.floor_i32());
+                        let local = voxel - origin;
+                        assert!(local.y >= 0 && local.y < CHUNK_SIZE.y as i32);
+                        assert_eq!(AIR, generated.chunk.get_voxel(local.map(|c| c as usize)));
+                    }
+                    n_flowers += generated.flowers.len();
+                }
+            }
+        }
+        assert!(n_flowers > 0);
+    }
+
+    #[test]
+    fn caves_under_the_surface() {
+        let terrain = NoiseTerrain::new(9);
+        let mut n_caves = 0;
+        for x in (0..256).step_by(3) {
+            for z in (0..256).step_by(3) {
+                let column = terrain.column(x, z);
+                for y in NoiseTerrain::CAVE_FLOOR..column.height {
+                    if terrain.voxel(column, vec3(x, y, z)) == AIR {
+                        assert!(y < column.height - NoiseTerrain::CAVE_ROOF_DEPTH);
+                        n_caves += 1;
+                    }
+                }
+            }
+        }
+        assert!(n_caves > 0);
+    }
+}
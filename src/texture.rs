@@ -1,5 +1,3 @@
-use image::GenericImageView;
-
 pub struct Texture {
     #[allow(unused)]
     pub texture: wgpu::Texture,
@@ -15,8 +13,16 @@ impl Texture {
         label: &str,
     ) -> Result<Self, image::ImageError> {
         let img = image::load_from_memory(bytes)?;
-        let rgba = img.to_rgba8();
-        let (width, height) = img.dimensions();
+        Ok(Self::from_image(device, queue, &img.to_rgba8(), label))
+    }
+
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        rgba: &image::RgbaImage,
+        label: &str,
+    ) -> Self {
+        let (width, height) = rgba.dimensions();
 
         let size = wgpu::Extent3d {
             width,
@@ -37,7 +43,7 @@ impl Texture {
 
         queue.write_texture(
             texture.as_image_copy(),
-            &rgba,
+            rgba,
             wgpu::TexelCopyBufferLayout {
                 offset: 0,
                 bytes_per_row: Some(4 * width),
@@ -57,11 +63,11 @@ impl Texture {
             ..Default::default()
         });
 
-        Ok(Self {
+        Self {
             texture,
             view,
             sampler,
-        })
+        }
     }
 }
 
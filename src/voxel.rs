@@ -1,119 +1,134 @@
+use std::sync::Arc;
+
 use cgmath::{Vector3, vec3};
 
 use crate::array_3d::Array3D;
+use crate::atlas::TextureAtlas;
+use crate::lighting::{UNLOADED_LIGHT, VoxelLight, light_color};
+use crate::material::material;
 use crate::render_util::Vertex;
 
-type VoxelType = u32;
+pub type VoxelType = u32;
+
+pub const CHUNK_SIZE: Vector3<usize> = vec3(32, 32, 32);
 
-struct CubeFaceDescription {
-    render_posx_face: bool,
-    render_negx_face: bool,
-    render_posy_face: bool,
-    render_negy_face: bool,
-    render_posz_face: bool,
-    render_negz_face: bool,
+pub const VOXEL_SCALE: f32 = 0.5;
+
+/// An indexed triangle list.
+#[derive(Clone, Default)]
+pub struct ChunkMesh {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
 }
 
-fn create_cube_mesh(offset: Vector3<f32>, size: Vector3<f32>, face_description: CubeFaceDescription) -> Vec<Vertex> {
-    //      +Y
-    //       |
-    //       2 -------- 6
-    //      /|         /|
-    //     / |        / |
-    //    4 -------- 7  |
-    //    |  |       |  |
-    //    |  0 ------|- 3 --- +X
-    //    | /        | /
-    //    |/         |/
-    //    1 -------- 5
-    //   /
-    // +Z
-    let positions = [
-        [offset.x, offset.y, offset.z],
-        [offset.x, offset.y, offset.z + size.z],
-        [offset.x, offset.y + size.y, offset.z],
-        [offset.x + size.x, offset.y, offset.z],
-        [offset.x, offset.y + size.y, offset.z + size.z],
-        [offset.x + size.x, offset.y, offset.z + size.z],
-        [offset.x + size.x, offset.y + size.y, offset.z],
-        [offset.x + size.x, offset.y + size.y, offset.z + size.z],
-    ];
-    let mut verts = vec![];
-    if face_description.render_negx_face {
-        const NORMAL: [f32; 3] = [-1.0, 0.0, 0.0];
-        verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[1], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[4], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[4], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[2], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL });
-    }
-    if face_description.render_negy_face {
-        const NORMAL: [f32; 3] = [0.0, -1.0, 0.0];
-        verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[5], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[1], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[3], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[5], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL });
-    }
-    if face_description.render_negz_face {
-        const NORMAL: [f32; 3] = [0.0, 0.0, -1.0];
-        verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[6], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[3], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[2], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[6], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL });
-    }
-    if face_description.render_posx_face {
-        const NORMAL: [f32; 3] = [1.0, 0.0, 0.0];
-        verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[3], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[6], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[5], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[3], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL });
-    }
-    if face_description.render_posy_face {
-        const NORMAL: [f32; 3] = [0.0, 1.0, 0.0];
-        verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[6], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[2], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[2], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[4], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL });
-    }
-    if face_description.render_posz_face {
-        const NORMAL: [f32; 3] = [0.0, 0.0, 1.0];
-        verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[4], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[1], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[1], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL });
-        verts.push(Vertex { position: positions[5], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL });
-    }
-    verts
+// Adds one quad covering `width` by `height` faces, starting at the voxel corner `origin`.
+fn push_quad(mesh: &mut ChunkMesh, atlas: &TextureAtlas, origin: Vector3<i32>, axis: usize, sign: i32, size: (i32, i32), face: (VoxelType, VoxelLight)) {
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let (voxel, light) = face;
+    let light = light_color(light);
+    let material = material(voxel);
+    let texture = match (axis, sign) {
+        (1, 1) => material.textures.top,
+        (1, _) => material.textures.bottom,
+        _ => material.textures.side,
+    };
+    let atlas_rect = atlas.get(texture).to_array();
+    let mut normal = [0.0; 3];
+    normal[axis] = sign as f32;
+    let corner = |du: i32, dv: i32| {
+        let mut corner = origin;
+        corner[u] += du;
+        corner[v] += dv;
+        corner
+    };
+    let corners = [corner(0, 0), corner(size.0, 0), corner(size.0, size.1), corner(0, size.1)];
+    let top = corners.iter().map(|c| c.y).max().unwrap();
+    let base = mesh.vertices.len() as u32;
+    for c in corners {
+        // One unit of UV per voxel, so the tile repeats. Side textures stay upright.
+        let uv = match axis {
+            0 => [c.z as f32, (top - c.y) as f32],
+            1 => [c.x as f32, c.z as f32],
+            _ => [c.x as f32, (top - c.y) as f32],
+        };
+        let position = [c.x as f32 * VOXEL_SCALE, c.y as f32 * VOXEL_SCALE, c.z as f32 * VOXEL_SCALE];
+        mesh.vertices.push(Vertex { position, light, normal, uv, color: material.color, atlas_rect });
+    }
+    // Counter-clockwise seen from the side the normal points to.
+    let order = if sign > 0 { [0, 1, 2, 0, 2, 3] } else { [0, 2, 1, 0, 3, 2] };
+    mesh.indices.extend(order.map(|i| base + i));
 }
 
-pub const CHUNK_SIZE: Vector3<usize> = vec3(32, 32, 32);
+/// The directions of the six faces of a voxel, in the order `NeighborChunks` stores them.
+pub const FACE_DIRECTIONS: [Vector3<i32>; 6] = [
+    vec3(1, 0, 0), vec3(-1, 0, 0),
+    vec3(0, 1, 0), vec3(0, -1, 0),
+    vec3(0, 0, 1), vec3(0, 0, -1),
+];
 
-pub const VOXEL_SCALE: f32 = 0.5;
+/// Read-only views of the chunks next to one being meshed, so faces on the shared borders can be
+/// culled. `None` where no chunk is loaded.
+#[derive(Default)]
+pub struct NeighborChunks<'a> {
+    chunks: [Option<&'a VoxelChunk>; 6],
+}
 
-const VOXEL_SIZE: Vector3<f32> = vec3(VOXEL_SCALE, VOXEL_SCALE, VOXEL_SCALE);
+impl<'a> NeighborChunks<'a> {
+    /// Looks up the chunk in each of `FACE_DIRECTIONS`.
+    pub fn new(get: impl FnMut(Vector3<i32>) -> Option<&'a VoxelChunk>) -> Self {
+        NeighborChunks { chunks: FACE_DIRECTIONS.map(get) }
+    }
+
+    fn get(&self, direction: Vector3<i32>) -> Option<&'a VoxelChunk> {
+        let index = FACE_DIRECTIONS.iter().position(|&d| d == direction).unwrap();
+        self.chunks[index]
+    }
+}
 
 pub struct VoxelChunk {
-    voxels: Array3D<VoxelType>,
-    per_voxel_vertices: Array3D<Vec<Vertex>>,
-    geometry_dirty: bool,
+    // Shared with snapshots being meshed or saved on other threads. Editing copies them first if
+    // any snapshot still holds them.
+    voxels: Arc<Array3D<VoxelType>>,
+    light: Arc<Array3D<VoxelLight>>,
+    // Where voxel (0, 0, 0) is in the world. The mesh is built in world space.
+    origin: Vector3<i32>,
+    mesh: ChunkMesh,
+    // Goes up every time the mesh is rebuilt, so the renderer knows when to upload it again.
+    mesh_generation: u64,
+    // Goes up on every change the mesh depends on. The mesh is dirty until it's been built from
+    // the latest version.
+    version: u64,
+    meshed_version: u64,
+    // Whether any voxel changed since the chunk was generated or loaded.
+    modified: bool,
 }
 
 impl VoxelChunk {
     pub fn new() -> Self {
         VoxelChunk {
-            voxels: Array3D::new(CHUNK_SIZE),
-            per_voxel_vertices: Array3D::new(CHUNK_SIZE),
-            geometry_dirty: true,
+            voxels: Arc::new(Array3D::new(CHUNK_SIZE)),
+            light: Arc::new(Array3D::new(CHUNK_SIZE)),
+            origin: vec3(0, 0, 0),
+            mesh: ChunkMesh::default(),
+            mesh_generation: 0,
+            version: 1,
+            meshed_version: 0,
+            modified: false,
+        }
+    }
+
+    /// A copy without the mesh, to build a mesh from or save on another thread. The voxels aren't
+    /// copied until one side changes them.
+    pub fn snapshot(&self) -> VoxelChunk {
+        VoxelChunk {
+            voxels: self.voxels.clone(),
+            light: self.light.clone(),
+            origin: self.origin,
+            mesh: ChunkMesh::default(),
+            mesh_generation: 0,
+            version: self.version,
+            meshed_version: 0,
+            modified: self.modified,
         }
     }
 
@@ -130,65 +145,465 @@ impl VoxelChunk {
     }
 
     pub fn set_voxel(&mut self, coord: Vector3<usize>, value: VoxelType) {
-        self.voxels.set(coord, value);
-        self.geometry_dirty = true;
+        Arc::make_mut(&mut self.voxels).set(coord, value);
+        self.modified = true;
+        self.mark_mesh_dirty();
+    }
+
+    /// Whether any voxel changed since the chunk was generated or loaded, so it needs saving to
+    /// keep the change.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn clear_modified(&mut self) {
+        self.modified = false;
+    }
+
+    pub fn set_origin(&mut self, origin: Vector3<i32>) {
+        if self.origin != origin {
+            self.origin = origin;
+            self.mark_mesh_dirty();
+        }
     }
 
-    fn is_face_visible(&self, voxel_position: Vector3<i32>, face_direction: Vector3<i32>) -> bool {
+    fn is_face_visible(&self, voxel_position: Vector3<i32>, face_direction: Vector3<i32>, neighbors: &NeighborChunks) -> bool {
+        let adjacent_position = voxel_position + face_direction;
+        let adjacent = if self.voxels.is_i32_out_of_bounds(adjacent_position) {
+            // Faces against a chunk that isn't loaded are drawn, since it could be anything.
+            let Some(neighbor) = neighbors.get(face_direction) else {
+                return true;
+            };
+            neighbor.get_voxel_i32(adjacent_position.zip(CHUNK_SIZE.map(|s| s as i32), i32::rem_euclid))
+        } else {
+            *self.voxels.get_i32(adjacent_position)
+        };
+        // Faces between two voxels of the same see-through material, like water, are hidden too.
+        !material(adjacent).is_opaque && adjacent != *self.voxels.get_i32(voxel_position)
+    }
+
+    // Faces are lit by the voxel in front of them, since the voxel they're on is usually solid and
+    // dark.
+    fn face_light(&self, voxel_position: Vector3<i32>, face_direction: Vector3<i32>, neighbors: &NeighborChunks) -> VoxelLight {
         let adjacent_position = voxel_position + face_direction;
         if self.voxels.is_i32_out_of_bounds(adjacent_position) {
-            return true;
+            let Some(neighbor) = neighbors.get(face_direction) else {
+                return UNLOADED_LIGHT;
+            };
+            *neighbor.light.get_i32(adjacent_position.zip(CHUNK_SIZE.map(|s| s as i32), i32::rem_euclid))
+        } else {
+            *self.light.get_i32(adjacent_position)
+        }
+    }
+
+    // Merges the visible faces in each slice of the chunk into as few rectangles as it can, going
+    // along rows first. Faces only merge when they have the same material and light, so the result
+    // looks the same as drawing each face on its own.
+    pub fn build_mesh(&self, atlas: &TextureAtlas, neighbors: &NeighborChunks) -> ChunkMesh {
+        let mut mesh = ChunkMesh::default();
+        let size = self.voxels.size.map(|s| s as i32);
+        for axis in 0..3 {
+            let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+            let index = |i: i32, j: i32| (j * size[u] + i) as usize;
+            for sign in [1, -1] {
+                let mut direction = vec3(0, 0, 0);
+                direction[axis] = sign;
+                for slice in 0..size[axis] {
+                    let mut mask = Vec::with_capacity((size[u] * size[v]) as usize);
+                    for j in 0..size[v] {
+                        for i in 0..size[u] {
+                            let mut coord = vec3(0, 0, 0);
+                            (coord[axis], coord[u], coord[v]) = (slice, i, j);
+                            let voxel = *self.voxels.get_i32(coord);
+                            let visible = material(voxel).is_visible && self.is_face_visible(coord, direction, neighbors);
+                            mask.push(visible.then(|| (voxel, self.face_light(coord, direction, neighbors))));
+                        }
+                    }
+                    for j in 0..size[v] {
+                        let mut i = 0;
+                        while i < size[u] {
+                            let Some(face) = mask[index(i, j)] else {
+                                i += 1;
+                                continue;
+                            };
+                            let mut width = 1;
+                            while i + width < size[u] && mask[index(i + width, j)] == Some(face) {
+                                width += 1;
+                            }
+                            let mut height = 1;
+                            while j + height < size[v] && (i..i + width).all(|k| mask[index(k, j + height)] == Some(face)) {
+                                height += 1;
+                            }
+                            for jj in j..j + height {
+                                for ii in i..i + width {
+                                    mask[index(ii, jj)] = None;
+                                }
+                            }
+                            let mut origin = vec3(0, 0, 0);
+                            (origin[axis], origin[u], origin[v]) = (slice + (sign > 0) as i32, i, j);
+                            push_quad(&mut mesh, atlas, self.origin + origin, axis, sign, (width, height), face);
+                            i += width;
+                        }
+                    }
+                }
+            }
+        }
+        mesh
+    }
+
+    pub fn get_voxel_light(&self, coord: Vector3<usize>) -> VoxelLight {
+        *self.light.get(coord)
+    }
+
+    pub fn set_voxel_light(&mut self, coord: Vector3<usize>, light: VoxelLight) {
+        if *self.light.get(coord) != light {
+            Arc::make_mut(&mut self.light).set(coord, light);
+            self.mark_mesh_dirty();
+        }
+    }
+
+    /// Makes every voxel dark.
+    pub fn clear_light(&mut self) {
+        self.light = Arc::new(Array3D::new(CHUNK_SIZE));
+        self.mark_mesh_dirty();
+    }
+
+    /// Whether anything the mesh depends on changed since it was last built, including voxels on
+    /// the borders of neighbouring chunks.
+    pub fn is_mesh_dirty(&self) -> bool {
+        self.meshed_version != self.version
+    }
+
+    pub fn mark_mesh_dirty(&mut self) {
+        self.version += 1;
+    }
+
+    /// Identifies the state the mesh is built from. See `set_mesh`.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Stores a mesh from `build_mesh`, built when the chunk was at `version`, and moves on to the
+    /// next generation. The chunk stays dirty if it changed since.
+    pub fn set_mesh(&mut self, mesh: ChunkMesh, version: u64) {
+        self.mesh = mesh;
+        self.mesh_generation += 1;
+        self.meshed_version = version;
+    }
+
+    /// The last mesh set, and its generation, which changes whenever the mesh does.
+    pub fn get_mesh(&self) -> (&ChunkMesh, u64) {
+        (&self.mesh, self.mesh_generation)
+    }
+}
+
+// The mesher from before greedy meshing, kept as a reference for the greedy mesher's tests.
+#[cfg(test)]
+mod naive {
+    use super::*;
+
+    const VOXEL_SIZE: Vector3<f32> = vec3(VOXEL_SCALE, VOXEL_SCALE, VOXEL_SCALE);
+
+    struct CubeFaceDescription {
+        render_posx_face: bool,
+        render_negx_face: bool,
+        render_posy_face: bool,
+        render_negy_face: bool,
+        render_posz_face: bool,
+        render_negz_face: bool,
+    }
+
+    /// Atlas rects, as stored in `Vertex::atlas_rect`, for each side of a cube.
+    struct FaceRects {
+        top: [f32; 4],
+        side: [f32; 4],
+        bottom: [f32; 4],
+    }
+
+    fn create_cube_mesh(offset: Vector3<f32>, size: Vector3<f32>, color: [f32; 3], face_rects: FaceRects, face_description: CubeFaceDescription) -> Vec<Vertex> {
+        //      +Y
+        //       |
+        //       2 -------- 6
+        //      /|         /|
+        //     / |        / |
+        //    4 -------- 7  |
+        //    |  |       |  |
+        //    |  0 ------|- 3 --- +X
+        //    | /        | /
+        //    |/         |/
+        //    1 -------- 5
+        //   /
+        // +Z
+        let positions = [
+            [offset.x, offset.y, offset.z],
+            [offset.x, offset.y, offset.z + size.z],
+            [offset.x, offset.y + size.y, offset.z],
+            [offset.x + size.x, offset.y, offset.z],
+            [offset.x, offset.y + size.y, offset.z + size.z],
+            [offset.x + size.x, offset.y, offset.z + size.z],
+            [offset.x + size.x, offset.y + size.y, offset.z],
+            [offset.x + size.x, offset.y + size.y, offset.z + size.z],
+        ];
+        let mut verts = vec![];
+        if face_description.render_negx_face {
+            let atlas_rect = face_rects.side;
+            const NORMAL: [f32; 3] = [-1.0, 0.0, 0.0];
+            verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[1], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[4], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[4], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[2], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL, color, atlas_rect });
+        }
+        if face_description.render_negy_face {
+            let atlas_rect = face_rects.bottom;
+            const NORMAL: [f32; 3] = [0.0, -1.0, 0.0];
+            verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[5], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[1], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[3], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[5], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL, color, atlas_rect });
+        }
+        if face_description.render_negz_face {
+            let atlas_rect = face_rects.side;
+            const NORMAL: [f32; 3] = [0.0, 0.0, -1.0];
+            verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[6], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[3], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[0], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[2], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[6], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL, color, atlas_rect });
         }
-        return *self.voxels.get_i32(adjacent_position) == 0;
+        if face_description.render_posx_face {
+            let atlas_rect = face_rects.side;
+            const NORMAL: [f32; 3] = [1.0, 0.0, 0.0];
+            verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[3], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[6], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[5], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[3], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL, color, atlas_rect });
+        }
+        if face_description.render_posy_face {
+            let atlas_rect = face_rects.top;
+            const NORMAL: [f32; 3] = [0.0, 1.0, 0.0];
+            verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[6], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[2], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[2], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[4], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL, color, atlas_rect });
+        }
+        if face_description.render_posz_face {
+            let atlas_rect = face_rects.side;
+            const NORMAL: [f32; 3] = [0.0, 0.0, 1.0];
+            verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[4], light: [0.0, 0.0, 0.0], uv: [0.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[1], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[7], light: [0.0, 0.0, 0.0], uv: [1.0, 0.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[1], light: [0.0, 0.0, 0.0], uv: [0.0, 1.0], normal: NORMAL, color, atlas_rect });
+            verts.push(Vertex { position: positions[5], light: [0.0, 0.0, 0.0], uv: [1.0, 1.0], normal: NORMAL, color, atlas_rect });
+        }
+        verts
     }
 
-    fn create_voxel_vertices(&self, coord: Vector3<i32>) -> Vec<Vertex> {
-        if *self.voxels.get_i32(coord) <= 0 {
+    // Adds an unindexed triangle list.
+    fn append_triangles(mesh: &mut ChunkMesh, vertices: &[Vertex]) {
+        let base = mesh.vertices.len() as u32;
+        mesh.indices.extend(base..base + vertices.len() as u32);
+        mesh.vertices.extend_from_slice(vertices);
+    }
+
+    fn create_voxel_vertices(chunk: &VoxelChunk, coord: Vector3<i32>, atlas: &TextureAtlas, neighbors: &NeighborChunks) -> Vec<Vertex> {
+        let material = material(*chunk.voxels.get_i32(coord));
+        if !material.is_visible {
             return vec![];
         }
         let offset = vec3(coord.x as f32 * VOXEL_SIZE.x, coord.y as f32 * VOXEL_SIZE.y, coord.z as f32 * VOXEL_SIZE.z);
         let face_description = CubeFaceDescription {
-            render_posx_face: self.is_face_visible(coord, vec3(1, 0, 0)),
-            render_negx_face: self.is_face_visible(coord, vec3(-1, 0, 0)),
-            render_posy_face: self.is_face_visible(coord, vec3(0, 1, 0)),
-            render_negy_face: self.is_face_visible(coord, vec3(0, -1, 0)),
-            render_posz_face: self.is_face_visible(coord, vec3(0, 0, 1)),
-            render_negz_face: self.is_face_visible(coord, vec3(0, 0, -1)),
+            render_posx_face: chunk.is_face_visible(coord, vec3(1, 0, 0), neighbors),
+            render_negx_face: chunk.is_face_visible(coord, vec3(-1, 0, 0), neighbors),
+            render_posy_face: chunk.is_face_visible(coord, vec3(0, 1, 0), neighbors),
+            render_negy_face: chunk.is_face_visible(coord, vec3(0, -1, 0), neighbors),
+            render_posz_face: chunk.is_face_visible(coord, vec3(0, 0, 1), neighbors),
+            render_negz_face: chunk.is_face_visible(coord, vec3(0, 0, -1), neighbors),
+        };
+        let face_rects = FaceRects {
+            top: atlas.get(material.textures.top).to_array(),
+            side: atlas.get(material.textures.side).to_array(),
+            bottom: atlas.get(material.textures.bottom).to_array(),
         };
-        create_cube_mesh(offset, VOXEL_SIZE, face_description)
+        create_cube_mesh(offset, VOXEL_SIZE, material.color, face_rects, face_description)
     }
 
-    fn rebuild_all_vertices(&mut self) {
-        for i in 0..self.voxels.size.x as i32 {
-            for j in 0..self.voxels.size.y as i32 {
-                for k in 0..self.voxels.size.z as i32 {
+    // One quad per visible face. The greedy mesher should cover exactly the same surface.
+    pub fn build_naive_mesh(chunk: &VoxelChunk, atlas: &TextureAtlas, neighbors: &NeighborChunks) -> ChunkMesh {
+        let mut mesh = ChunkMesh::default();
+        for i in 0..chunk.voxels.size.x as i32 {
+            for j in 0..chunk.voxels.size.y as i32 {
+                for k in 0..chunk.voxels.size.z as i32 {
                     let coord = vec3(i, j, k);
-                    let verts = self.create_voxel_vertices(coord);
-                    self.per_voxel_vertices.set_i32(coord, verts);
+                    let mut verts = create_voxel_vertices(chunk, coord, atlas, neighbors);
+                    for vert in verts.iter_mut() {
+                        let direction = Vector3::from(vert.normal).map(|n| n as i32);
+                        vert.light = light_color(chunk.face_light(coord, direction, neighbors));
+                    }
+                    append_triangles(&mut mesh, &verts);
                 }
             }
         }
-        self.geometry_dirty = false;
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use cgmath::InnerSpace;
+
+    use super::*;
+
+    use crate::material::{AIR, DIRT, SAND, STONE, WATER};
+
+    // Normal, distance of the plane along it, atlas rect and light.
+    type FaceKey = ([i32; 3], i32, [u32; 4], [u32; 3]);
+
+    // Total area of the triangles in a mesh, for each plane, texture and light they're drawn with.
+    fn area_by_face(mesh: &ChunkMesh) -> HashMap<FaceKey, f32> {
+        let mut areas = HashMap::new();
+        for triangle in mesh.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices[triangle[i] as usize]);
+            let [a_pos, b_pos, c_pos] = [a, b, c].map(|v| Vector3::from(v.position));
+            let cross = (b_pos - a_pos).cross(c_pos - a_pos);
+            let normal = Vector3::from(a.normal);
+            // Also checks the winding: the triangle has to face the way its normal does.
+            assert!(cross.dot(normal) > 0.0);
+            let plane = (a_pos.dot(normal) / VOXEL_SCALE).round() as i32;
+            let key = (a.normal.map(|n| n as i32), plane, a.atlas_rect.map(f32::to_bits), a.light.map(f32::to_bits));
+            *areas.entry(key).or_insert(0.0) += cross.dot(normal) / 2.0;
+        }
+        areas
     }
 
-    pub fn set_voxel_light(&mut self, coord: Vector3<usize>, light: [f32; 3]) {
-        for vert in self.per_voxel_vertices.get_mut(coord) {
-            vert.light = light;
+    fn remesh<'a>(chunk: &'a mut VoxelChunk, atlas: &TextureAtlas) -> (&'a ChunkMesh, u64) {
+        if chunk.is_mesh_dirty() {
+            chunk.set_mesh(chunk.build_mesh(atlas, &NeighborChunks::default()), chunk.version());
         }
+        chunk.get_mesh()
     }
 
-    pub fn get_vertices(&mut self) -> Vec<Vertex> {
-        if self.geometry_dirty {
-            self.rebuild_all_vertices();
+    fn assert_same_surface(chunk: &VoxelChunk, atlas: &TextureAtlas) {
+        assert_same_surface_with(chunk, atlas, &NeighborChunks::default());
+    }
+
+    fn assert_same_surface_with(chunk: &VoxelChunk, atlas: &TextureAtlas, neighbors: &NeighborChunks) {
+        let naive = area_by_face(&naive::build_naive_mesh(chunk, atlas, neighbors));
+        let greedy = area_by_face(&chunk.build_mesh(atlas, neighbors));
+        assert_eq!(naive.len(), greedy.len());
+        for (key, area) in naive {
+            assert!((area - greedy[&key]).abs() < 1e-3, "{:?}: {} vs {}", key, area, greedy[&key]);
         }
-        let mut result = vec![];
-        for i in 0..self.per_voxel_vertices.size.x {
-            for j in 0..self.per_voxel_vertices.size.y {
-                for k in 0..self.per_voxel_vertices.size.z {
-                    result.extend_from_slice(self.per_voxel_vertices.get(vec3(i, j, k)));
+    }
+
+    #[test]
+    fn greedy_covers_same_surface_as_naive() {
+        let atlas = TextureAtlas::new();
+        let mut chunk = VoxelChunk::new();
+        let kinds = [AIR, AIR, DIRT, STONE, SAND, WATER];
+        let mut state = 12345u32;
+        for i in 0..CHUNK_SIZE.x {
+            for j in 0..8 {
+                for k in 0..CHUNK_SIZE.z {
+                    state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                    let coord = vec3(i, j, k);
+                    chunk.set_voxel(coord, kinds[(state >> 24) as usize % kinds.len()]);
+                    chunk.set_voxel_light(coord, [[15, 0, 0, 0], [8, 3, 0, 0]][(state >> 16) as usize % 2]);
+                }
+            }
+        }
+        assert_same_surface(&chunk, &atlas);
+    }
+
+    #[test]
+    fn flat_slab_is_six_quads() {
+        let atlas = TextureAtlas::new();
+        let mut chunk = VoxelChunk::new();
+        for i in 0..CHUNK_SIZE.x {
+            for j in 0..3 {
+                for k in 0..CHUNK_SIZE.z {
+                    chunk.set_voxel(vec3(i, j, k), STONE);
                 }
             }
         }
-        result
+        assert_same_surface(&chunk, &atlas);
+        let (mesh, _) = remesh(&mut chunk, &atlas);
+        assert_eq!(6 * 4, mesh.vertices.len());
+        assert_eq!(6 * 6, mesh.indices.len());
+    }
+
+    #[test]
+    fn light_change_splits_quads() {
+        let atlas = TextureAtlas::new();
+        let mut chunk = VoxelChunk::new();
+        for i in 0..4 {
+            chunk.set_voxel(vec3(i, 0, 0), DIRT);
+        }
+        let (mesh, before_generation) = remesh(&mut chunk, &atlas);
+        let before = mesh.indices.len();
+        assert_eq!(before_generation, remesh(&mut chunk, &atlas).1);
+        // Lighting the air above one voxel lights its top face.
+        chunk.set_voxel_light(vec3(1, 1, 0), [8, 0, 0, 0]);
+        let (mesh, after_generation) = remesh(&mut chunk, &atlas);
+        assert!(mesh.indices.len() > before);
+        assert!(after_generation != before_generation);
+        assert_same_surface(&chunk, &atlas);
+    }
+
+    #[test]
+    fn culls_against_neighbors() {
+        let atlas = TextureAtlas::new();
+        let mut chunk = VoxelChunk::new();
+        let mut neighbor = VoxelChunk::new();
+        for j in 0..CHUNK_SIZE.y {
+            for k in 0..CHUNK_SIZE.z {
+                chunk.set_voxel(vec3(CHUNK_SIZE.x - 1, j, k), STONE);
+                neighbor.set_voxel(vec3(0, j, k), if j < 16 { STONE } else { WATER });
+            }
+        }
+        chunk.set_voxel(vec3(CHUNK_SIZE.x - 1, 20, 4), WATER);
+        // In voxels, of faces pointing at the neighbour.
+        let facing_area = |mesh: &ChunkMesh| {
+            let areas = area_by_face(mesh);
+            areas.iter().filter(|(key, _)| key.0 == [1, 0, 0]).map(|(_, area)| area).sum::<f32>() / (VOXEL_SCALE * VOXEL_SCALE)
+        };
+        let alone = chunk.build_mesh(&atlas, &NeighborChunks::default());
+        assert!((facing_area(&alone) - (CHUNK_SIZE.y * CHUNK_SIZE.z) as f32).abs() < 1e-3);
+
+        let neighbors = NeighborChunks::new(|direction| (direction == vec3(1, 0, 0)).then_some(&neighbor));
+        assert_same_surface_with(&chunk, &atlas, &neighbors);
+        // Stone against stone is hidden. Stone against water is not, but water against water is.
+        let visible = facing_area(&chunk.build_mesh(&atlas, &neighbors));
+        let expected = ((CHUNK_SIZE.y - 16) * CHUNK_SIZE.z - 1) as f32;
+        assert!((visible - expected).abs() < 1e-3, "{} vs {}", visible, expected);
+    }
+
+    #[test]
+    fn stale_mesh_stays_dirty() {
+        let atlas = TextureAtlas::new();
+        let mut chunk = VoxelChunk::new();
+        chunk.set_voxel(vec3(1, 1, 1), STONE);
+        let snapshot = chunk.snapshot();
+        // Edited while the snapshot is being meshed, which mustn't change the snapshot.
+        chunk.set_voxel(vec3(2, 1, 1), STONE);
+        assert_eq!(AIR, snapshot.get_voxel(vec3(2, 1, 1)));
+
+        chunk.set_mesh(snapshot.build_mesh(&atlas, &NeighborChunks::default()), snapshot.version());
+        assert!(chunk.is_mesh_dirty());
+        assert_eq!(1, chunk.get_mesh().1);
+        let version = chunk.version();
+        chunk.set_mesh(chunk.build_mesh(&atlas, &NeighborChunks::default()), version);
+        assert!(!chunk.is_mesh_dirty());
     }
 }
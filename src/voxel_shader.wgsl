@@ -1,6 +1,5 @@
 // Vertex shader
 
-const FACE_COLOR: vec3<f32> = vec3(1.0, 0.81568627, 0.50196078);
 const LIGHT_DIRECTION: vec3<f32> = vec3(0.18814417, -0.94072087, 0.28221626);
 
 struct CameraUniform {
@@ -16,12 +15,16 @@ struct VertexInput {
     @location(1) light: vec3<f32>,
     @location(2) normal: vec3<f32>,
     @location(3) uv: vec2<f32>,
+    @location(4) color: vec3<f32>,
+    @location(5) atlas_rect: vec4<f32>,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) uv: vec2<f32>,
     @location(1) light: vec3<f32>,
+    @location(2) color: vec3<f32>,
+    @location(3) atlas_rect: vec4<f32>,
 };
 
 
@@ -33,6 +36,8 @@ fn vs_main(
     out.clip_position = camera.view_projection * vec4<f32>(model.position, 1.0);
     out.uv = model.uv;
     out.light = model.light;
+    out.color = model.color;
+    out.atlas_rect = model.atlas_rect;
     return out;
 }
 
@@ -41,8 +46,10 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let texture_color = textureSample(texture_view, texture_sampler, in.uv);
-    let blended_color = mix(FACE_COLOR, texture_color.rgb, 0.35);
+    // The UV repeats within the face's tile of the atlas.
+    let atlas_uv = in.atlas_rect.xy + fract(in.uv) * (in.atlas_rect.zw - in.atlas_rect.xy);
+    let texture_color = textureSample(texture_view, texture_sampler, atlas_uv);
+    let blended_color = mix(in.color, texture_color.rgb, 0.35);
     let lit_color = blended_color * in.light;
     return vec4<f32>(lit_color, 1.0);
 }
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use cgmath::{Vector3, vec3};
+
+use crate::lighting::VoxelLight;
+use crate::voxel::{CHUNK_SIZE, FACE_DIRECTIONS, VoxelChunk, VoxelType};
+
+fn chunk_size_i32() -> Vector3<i32> {
+    CHUNK_SIZE.map(|s| s as i32)
+}
+
+/// The chunk a voxel is in. Works for negative coordinates too, so voxel -1 is in chunk -1.
+pub fn voxel_to_chunk(coord: Vector3<i32>) -> Vector3<i32> {
+    coord.zip(chunk_size_i32(), i32::div_euclid)
+}
+
+/// Where a voxel is within its chunk.
+pub fn voxel_to_local(coord: Vector3<i32>) -> Vector3<usize> {
+    coord.zip(chunk_size_i32(), i32::rem_euclid).map(|c| c as usize)
+}
+
+/// The world coordinate of a chunk's first voxel.
+pub fn chunk_origin(chunk_coord: Vector3<i32>) -> Vector3<i32> {
+    chunk_coord.zip(chunk_size_i32(), |c, s| c * s)
+}
+
+/// Chunks keyed by their chunk coordinate. Voxel coordinates are world-wide, and anything in a chunk
+/// that isn't loaded reads as `None`.
+pub struct VoxelWorld {
+    chunks: HashMap<Vector3<i32>, VoxelChunk>,
+    // The box every loaded chunk is in, in chunk coordinates, inclusive.
+    chunk_bounds: Option<(Vector3<i32>, Vector3<i32>)>,
+}
+
+impl VoxelWorld {
+    pub fn new() -> Self {
+        VoxelWorld {
+            chunks: HashMap::new(),
+            chunk_bounds: None,
+        }
+    }
+
+    // The chunks next to one that was added or removed have new faces to show or hide.
+    fn mark_neighbors_dirty(&mut self, chunk_coord: Vector3<i32>) {
+        for direction in FACE_DIRECTIONS {
+            if let Some(neighbor) = self.chunks.get_mut(&(chunk_coord + direction)) {
+                neighbor.mark_mesh_dirty();
+            }
+        }
+    }
+
+    /// Replaces any chunk already there.
+    pub fn insert_chunk(&mut self, chunk_coord: Vector3<i32>, mut chunk: VoxelChunk) {
+        chunk.set_origin(chunk_origin(chunk_coord));
+        chunk.mark_mesh_dirty();
+        self.chunks.insert(chunk_coord, chunk);
+        self.mark_neighbors_dirty(chunk_coord);
+        self.chunk_bounds = Some(match self.chunk_bounds {
+            Some((min, max)) => (min.zip(chunk_coord, i32::min), max.zip(chunk_coord, i32::max)),
+            None => (chunk_coord, chunk_coord),
+        });
+    }
+
+    pub fn remove_chunk(&mut self, chunk_coord: Vector3<i32>) -> Option<VoxelChunk> {
+        let chunk = self.chunks.remove(&chunk_coord)?;
+        self.mark_neighbors_dirty(chunk_coord);
+        self.chunk_bounds = self.chunks.keys().fold(None, |bounds, &c| Some(match bounds {
+            Some((min, max)) => (c.zip(min, i32::min), c.zip(max, i32::max)),
+            None => (c, c),
+        }));
+        Some(chunk)
+    }
+
+    pub fn get_chunk(&self, chunk_coord: Vector3<i32>) -> Option<&VoxelChunk> {
+        self.chunks.get(&chunk_coord)
+    }
+
+    pub fn get_chunk_mut(&mut self, chunk_coord: Vector3<i32>) -> Option<&mut VoxelChunk> {
+        self.chunks.get_mut(&chunk_coord)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = (Vector3<i32>, &VoxelChunk)> {
+        self.chunks.iter().map(|(&coord, chunk)| (coord, chunk))
+    }
+
+    pub fn chunks_mut(&mut self) -> impl Iterator<Item = (Vector3<i32>, &mut VoxelChunk)> {
+        self.chunks.iter_mut().map(|(&coord, chunk)| (coord, chunk))
+    }
+
+    /// The box every loaded voxel is in, as a minimum and an exclusive maximum. There may be
+    /// unloaded chunks inside it.
+    pub fn voxel_bounds(&self) -> Option<(Vector3<i32>, Vector3<i32>)> {
+        self.chunk_bounds.map(|(min, max)| (chunk_origin(min), chunk_origin(max + vec3(1, 1, 1))))
+    }
+
+    pub fn is_loaded(&self, coord: Vector3<i32>) -> bool {
+        self.chunks.contains_key(&voxel_to_chunk(coord))
+    }
+
+    /// `None` if the voxel's chunk isn't loaded.
+    pub fn get_voxel(&self, coord: Vector3<i32>) -> Option<VoxelType> {
+        let chunk = self.chunks.get(&voxel_to_chunk(coord))?;
+        Some(chunk.get_voxel(voxel_to_local(coord)))
+    }
+
+    /// The voxel next to `coord` in `direction`, which may be in the next chunk over.
+    pub fn get_neighbor(&self, coord: Vector3<i32>, direction: Vector3<i32>) -> Option<VoxelType> {
+        self.get_voxel(coord + direction)
+    }
+
+    /// Creates an empty chunk for the voxel first if its chunk isn't loaded.
+    pub fn set_voxel(&mut self, coord: Vector3<i32>, value: VoxelType) {
+        let chunk_coord = voxel_to_chunk(coord);
+        if !self.chunks.contains_key(&chunk_coord) {
+            self.insert_chunk(chunk_coord, VoxelChunk::new());
+        }
+        self.chunks.get_mut(&chunk_coord).unwrap().set_voxel(voxel_to_local(coord), value);
+        // A voxel on a border can hide or show a face in the chunk on the other side.
+        self.mark_across_border_dirty(coord);
+    }
+
+    fn mark_across_border_dirty(&mut self, coord: Vector3<i32>) {
+        let chunk_coord = voxel_to_chunk(coord);
+        for direction in FACE_DIRECTIONS {
+            let other = voxel_to_chunk(coord + direction);
+            if other != chunk_coord && let Some(neighbor) = self.chunks.get_mut(&other) {
+                neighbor.mark_mesh_dirty();
+            }
+        }
+    }
+
+    /// `None` if the voxel's chunk isn't loaded.
+    pub fn get_light(&self, coord: Vector3<i32>) -> Option<VoxelLight> {
+        let chunk = self.chunks.get(&voxel_to_chunk(coord))?;
+        Some(chunk.get_voxel_light(voxel_to_local(coord)))
+    }
+
+    /// Does nothing if the voxel's chunk isn't loaded.
+    pub fn set_light(&mut self, coord: Vector3<i32>, light: VoxelLight) {
+        let Some(chunk) = self.chunks.get_mut(&voxel_to_chunk(coord)) else {
+            return;
+        };
+        let local = voxel_to_local(coord);
+        if chunk.get_voxel_light(local) != light {
+            chunk.set_voxel_light(local, light);
+            // Faces in the chunk on the other side of a border are lit by it too.
+            self.mark_across_border_dirty(coord);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::atlas::TextureAtlas;
+    use crate::material::{AIR, DIRT, STONE};
+    use crate::voxel::{NeighborChunks, VOXEL_SCALE};
+
+    // Rebuilds the mesh of every chunk that needs it, the way `ChunkStreamer`'s workers do.
+    fn update_meshes(world: &mut VoxelWorld, atlas: &TextureAtlas) {
+        let dirty: Vec<_> = world.chunks().filter(|(_, chunk)| chunk.is_mesh_dirty()).map(|(chunk_coord, _)| chunk_coord).collect();
+        for chunk_coord in dirty {
+            let neighbors = NeighborChunks::new(|direction| world.get_chunk(chunk_coord + direction));
+            let chunk = world.get_chunk(chunk_coord).unwrap();
+            let (mesh, version) = (chunk.build_mesh(atlas, &neighbors), chunk.version());
+            world.get_chunk_mut(chunk_coord).unwrap().set_mesh(mesh, version);
+        }
+    }
+
+    #[test]
+    fn negative_coordinates() {
+        assert_eq!(vec3(-1, 0, -1), voxel_to_chunk(vec3(-1, 0, -32)));
+        assert_eq!(vec3(31, 0, 0), voxel_to_local(vec3(-1, 0, -32)));
+        assert_eq!(vec3(-2, 0, 0), voxel_to_chunk(vec3(-33, 31, 0)));
+        assert_eq!(vec3(-32, 0, 32), chunk_origin(vec3(-1, 0, 1)));
+
+        let mut world = VoxelWorld::new();
+        world.set_voxel(vec3(-1, -1, -1), STONE);
+        assert_eq!(Some(STONE), world.get_voxel(vec3(-1, -1, -1)));
+        assert_eq!(Some(AIR), world.get_voxel(vec3(-2, -1, -1)));
+        assert_eq!(None, world.get_voxel(vec3(0, -1, -1)));
+        assert_eq!(Some((vec3(-32, -32, -32), vec3(0, 0, 0))), world.voxel_bounds());
+    }
+
+    #[test]
+    fn neighbors_across_seams() {
+        let mut world = VoxelWorld::new();
+        world.set_voxel(vec3(31, 0, 0), DIRT);
+        world.set_voxel(vec3(32, 0, 0), STONE);
+        assert_eq!(Some(STONE), world.get_neighbor(vec3(31, 0, 0), vec3(1, 0, 0)));
+        assert_eq!(Some(DIRT), world.get_neighbor(vec3(32, 0, 0), vec3(-1, 0, 0)));
+        assert_eq!(None, world.get_neighbor(vec3(31, 0, 0), vec3(0, -1, 0)));
+    }
+
+    #[test]
+    fn bounds_shrink_on_remove() {
+        let mut world = VoxelWorld::new();
+        world.insert_chunk(vec3(0, 0, 0), VoxelChunk::new());
+        world.insert_chunk(vec3(2, 0, -1), VoxelChunk::new());
+        assert_eq!(Some((vec3(0, 0, -32), vec3(96, 32, 32))), world.voxel_bounds());
+        assert!(world.remove_chunk(vec3(2, 0, -1)).is_some());
+        assert_eq!(Some((vec3(0, 0, 0), vec3(32, 32, 32))), world.voxel_bounds());
+        assert!(world.remove_chunk(vec3(0, 0, 0)).is_some());
+        assert_eq!(None, world.voxel_bounds());
+        assert!(!world.is_loaded(vec3(0, 0, 0)));
+    }
+
+    #[test]
+    fn border_edits_dirty_neighbors() {
+        let atlas = TextureAtlas::new();
+        let mut world = VoxelWorld::new();
+        for chunk_coord in [vec3(0, 0, 0), vec3(1, 0, 0), vec3(0, 1, 0)] {
+            world.insert_chunk(chunk_coord, VoxelChunk::new());
+        }
+        update_meshes(&mut world, &atlas);
+        let is_dirty = |world: &VoxelWorld, chunk_coord| world.get_chunk(chunk_coord).unwrap().is_mesh_dirty();
+
+        world.set_voxel(vec3(5, 5, 5), STONE);
+        assert!(is_dirty(&world, vec3(0, 0, 0)));
+        assert!(!is_dirty(&world, vec3(1, 0, 0)));
+        update_meshes(&mut world, &atlas);
+
+        world.set_voxel(vec3(31, 31, 5), STONE);
+        assert!(is_dirty(&world, vec3(0, 0, 0)));
+        assert!(is_dirty(&world, vec3(1, 0, 0)));
+        assert!(is_dirty(&world, vec3(0, 1, 0)));
+        update_meshes(&mut world, &atlas);
+
+        world.remove_chunk(vec3(0, 1, 0));
+        assert!(is_dirty(&world, vec3(0, 0, 0)));
+        assert!(!is_dirty(&world, vec3(1, 0, 0)));
+    }
+
+    #[test]
+    fn culls_faces_on_seams() {
+        let atlas = TextureAtlas::new();
+        let mut world = VoxelWorld::new();
+        // A slab two chunks wide, so the only seam is between them.
+        for i in 0..64 {
+            for k in 0..32 {
+                world.set_voxel(vec3(i, 0, k), STONE);
+            }
+        }
+        update_meshes(&mut world, &atlas);
+        for chunk_coord in [vec3(0, 0, 0), vec3(1, 0, 0)] {
+            let (mesh, _) = world.get_chunk(chunk_coord).unwrap().get_mesh();
+            // Top, bottom, and the three outer sides.
+            assert_eq!(5 * 4, mesh.vertices.len());
+        }
+
+        // Digging out the voxel next to the seam shows the face behind it in the other chunk.
+        world.set_voxel(vec3(32, 0, 5), AIR);
+        update_meshes(&mut world, &atlas);
+        let (mesh, _) = world.get_chunk(vec3(0, 0, 0)).unwrap().get_mesh();
+        let x_max = 32.0 * VOXEL_SCALE;
+        assert!(mesh.vertices.iter().any(|v| v.normal == [1.0, 0.0, 0.0] && v.position[0] == x_max));
+    }
+}
@@ -1,9 +1,9 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Instant;
 
-use cgmath::{vec2, Vector2};
+use cgmath::{vec2, Vector2, Vector3};
 use pollster::FutureExt as _;
 use winit::application::ApplicationHandler;
 use winit::event::{DeviceEvent, DeviceId, ElementState, KeyEvent, MouseButton, WindowEvent};
@@ -14,9 +14,11 @@ use wgpu::util::DeviceExt;
 use wgpu_text::{glyph_brush::{Section as TextSection, OwnedText, ab_glyph::FontRef, OwnedSection}, BrushBuilder, TextBrush};
 
 use crate::camera::CameraUniform;
+use crate::atlas::TextureAtlas;
 use crate::game_state::GameState;
-use crate::render_util::{MovingAverage, Vertex};
+use crate::render_util::{DynamicVertexBuffer, LineVertex, MeshBuffers, MovingAverage, OverlayVertex, Vertex};
 use crate::texture::{DepthTexture, Texture};
+use crate::voxel::ChunkMesh;
 
 struct TimestampQueryState {
     query_set: wgpu::QuerySet,
@@ -34,6 +36,8 @@ struct RenderState<'a> {
     timestamp_query_state: Option<TimestampQueryState>,
     voxel_render_pipeline: wgpu::RenderPipeline,
     flower_render_pipeline: wgpu::RenderPipeline,
+    line_render_pipeline: wgpu::RenderPipeline,
+    overlay_render_pipeline: wgpu::RenderPipeline,
     depth_texture: DepthTexture,
     camera_uniform: CameraUniform,
     camera_buffer: wgpu::Buffer,
@@ -44,6 +48,11 @@ struct RenderState<'a> {
     #[allow(unused)]
     flower_texture: Texture,
     flower_texture_bind_group: wgpu::BindGroup,
+    crosshair_texture: Texture,
+    crosshair_texture_bind_group: wgpu::BindGroup,
+    /// One per loaded chunk, by chunk coordinate.
+    voxel_mesh_buffers: HashMap<Vector3<i32>, MeshBuffers>,
+    dynamic_vertex_buffer: DynamicVertexBuffer,
     #[allow(unused)]
     font: &'a [u8],
     text_brush: TextBrush<FontRef<'a>>,
@@ -51,7 +60,7 @@ struct RenderState<'a> {
 }
 
 impl RenderState<'_> {
-    async fn new(window: Window) -> Self {
+    async fn new(window: Window, atlas: &TextureAtlas) -> Self {
         let size = window.inner_size();
         let window_arc = Arc::new(window);
 
@@ -140,12 +149,14 @@ impl RenderState<'_> {
             label: Some("camera_bind_group"),
         });
 
-        let voxel_texture_bytes = include_bytes!("../textures/noise_128.png");
-        let voxel_texture = Texture::from_bytes(&device, &queue, voxel_texture_bytes, "voxel_texture").unwrap();
+        let voxel_texture = Texture::from_image(&device, &queue, &atlas.image, "voxel_texture");
 
         let flower_texture_bytes = include_bytes!("../textures/daisies2.png");
         let flower_texture = Texture::from_bytes(&device, &queue, flower_texture_bytes, "flower_texture").unwrap();
 
+        let crosshair_texture_bytes = include_bytes!("../textures/crosshairs.png");
+        let crosshair_texture = Texture::from_bytes(&device, &queue, crosshair_texture_bytes, "crosshair_texture").unwrap();
+
         let texture_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
                 wgpu::BindGroupLayoutEntry {
@@ -198,6 +209,21 @@ impl RenderState<'_> {
             label: Some("flower_texture_bind_group"),
         });
 
+        let crosshair_texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&crosshair_texture.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&crosshair_texture.view),
+                },
+            ],
+            label: Some("crosshair_texture_bind_group"),
+        });
+
         let depth_stencil_state = wgpu::DepthStencilState {
             format: DepthTexture::DEPTH_FORMAT,
             depth_write_enabled: true,
@@ -215,6 +241,15 @@ impl RenderState<'_> {
             bias: wgpu::DepthBiasState::default(),
         };
 
+        // Depth state for overlays: drawn over everything
+        let overlay_depth_stencil_state = wgpu::DepthStencilState {
+            format: DepthTexture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        };
+
         let font = include_bytes!("Rubik-Regular.ttf");
         let text_brush = BrushBuilder::using_font_bytes(font).unwrap()
             .with_depth_stencil(Some(depth_stencil_state.clone()))
@@ -337,6 +372,100 @@ impl RenderState<'_> {
             cache: None,
         });
 
+        let line_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Line Pipeline Layout"),
+                bind_group_layouts: &[&camera_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let line_shader = device.create_shader_module(wgpu::include_wgsl!("line_shader.wgsl"));
+        let line_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Line Render Pipeline"),
+            layout: Some(&line_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &line_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[LineVertex::buffer_layout()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &line_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(depth_stencil_state.clone()),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let overlay_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Overlay Pipeline Layout"),
+                bind_group_layouts: &[&texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let overlay_shader = device.create_shader_module(wgpu::include_wgsl!("overlay_shader.wgsl"));
+        let overlay_render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Overlay Render Pipeline"),
+            layout: Some(&overlay_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &overlay_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[OverlayVertex::buffer_layout()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &overlay_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(overlay_depth_stencil_state),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let dynamic_vertex_buffer = DynamicVertexBuffer::new(&device, 1 << 20);
+
         RenderState {
             window: window_arc,
             surface,
@@ -346,6 +475,8 @@ impl RenderState<'_> {
             timestamp_query_state,
             voxel_render_pipeline,
             flower_render_pipeline,
+            line_render_pipeline,
+            overlay_render_pipeline,
             depth_texture,
             camera_uniform,
             camera_buffer,
@@ -354,6 +485,10 @@ impl RenderState<'_> {
             voxel_texture_bind_group,
             flower_texture,
             flower_texture_bind_group,
+            crosshair_texture,
+            crosshair_texture_bind_group,
+            voxel_mesh_buffers: HashMap::new(),
+            dynamic_vertex_buffer,
             font,
             text_brush,
             text_section,
@@ -365,20 +500,38 @@ impl RenderState<'_> {
         self.text_brush.queue(&self.device, &self.queue, [&self.text_section]).unwrap();
     }
 
-    fn render(&mut self, voxel_vertices: &Vec<Vertex>, flower_vertices: &Vec<Vertex>) -> Result<(), wgpu::SurfaceError> {
-        let voxel_vertex_buffer = self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Voxel Vertex Buffer"),
-                contents: bytemuck::cast_slice(&voxel_vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
+    // A quad in the middle of the screen, at the crosshair texture's own size in pixels.
+    fn get_crosshair_vertices(&self) -> Vec<OverlayVertex> {
+        let half_width = self.crosshair_texture.texture.width() as f32 / self.config.width as f32;
+        let half_height = self.crosshair_texture.texture.height() as f32 / self.config.height as f32;
+        let top_left = OverlayVertex { position: [-half_width, half_height], uv: [0.0, 0.0] };
+        let top_right = OverlayVertex { position: [half_width, half_height], uv: [1.0, 0.0] };
+        let bottom_left = OverlayVertex { position: [-half_width, -half_height], uv: [0.0, 1.0] };
+        let bottom_right = OverlayVertex { position: [half_width, -half_height], uv: [1.0, 1.0] };
+        vec![
+            top_left, bottom_left, bottom_right,
+            bottom_right, top_right, top_left,
+        ]
+    }
 
-        let flower_vertex_buffer = self.device.create_buffer_init(
-            &wgpu::util::BufferInitDescriptor {
-                label: Some("Flower Vertex Buffer"),
-                contents: bytemuck::cast_slice(&flower_vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
+    // Uploads the meshes that changed, and drops the buffers of chunks that are gone.
+    fn update_voxel_meshes<'m>(&mut self, meshes: impl Iterator<Item = (Vector3<i32>, &'m ChunkMesh, u64)>) {
+        let mut loaded = HashSet::new();
+        for (coord, mesh, generation) in meshes {
+            let buffers = self.voxel_mesh_buffers.entry(coord).or_insert_with(|| MeshBuffers::new(&self.device, "Voxel Mesh Buffer"));
+            buffers.update(&self.device, &self.queue, generation, &mesh.vertices, &mesh.indices);
+            loaded.insert(coord);
+        }
+        self.voxel_mesh_buffers.retain(|coord, _| loaded.contains(coord));
+    }
+
+    fn render(&mut self, player_vertices: &[Vertex], flower_vertices: &[Vertex], outline_vertices: &[LineVertex]) -> Result<(), wgpu::SurfaceError> {
+        let crosshair_vertices = self.get_crosshair_vertices();
+        let player_range = self.dynamic_vertex_buffer.stage(player_vertices);
+        let flower_range = self.dynamic_vertex_buffer.stage(flower_vertices);
+        let outline_range = self.dynamic_vertex_buffer.stage(outline_vertices);
+        let crosshair_range = self.dynamic_vertex_buffer.stage(&crosshair_vertices);
+        self.dynamic_vertex_buffer.upload(&self.device, &self.queue);
 
         let output = self.surface.get_current_texture()?;
         let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
@@ -421,20 +574,41 @@ impl RenderState<'_> {
             render_pass.set_pipeline(&self.voxel_render_pipeline);
             render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
             render_pass.set_bind_group(1, &self.voxel_texture_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, voxel_vertex_buffer.slice(..));
-            let n_vertices = voxel_vertices.len() as u32;
-            render_pass.draw(0..n_vertices, 0..1);
+            for buffers in self.voxel_mesh_buffers.values() {
+                buffers.draw(&mut render_pass);
+            }
+
+            // Render the player model
+            if !player_vertices.is_empty() {
+                render_pass.set_vertex_buffer(0, self.dynamic_vertex_buffer.slice(player_range));
+                render_pass.draw(0..player_vertices.len() as u32, 0..1);
+            }
 
             // Render flowers
             if flower_vertices.len() > 0 {
                 render_pass.set_pipeline(&self.flower_render_pipeline);
                 render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
                 render_pass.set_bind_group(1, &self.flower_texture_bind_group, &[]);
-                render_pass.set_vertex_buffer(0, flower_vertex_buffer.slice(..));
+                render_pass.set_vertex_buffer(0, self.dynamic_vertex_buffer.slice(flower_range));
                 let n_vertices = flower_vertices.len() as u32;
                 render_pass.draw(0..n_vertices, 0..1);
             }
 
+            // Render the outline around the targeted voxel
+            if !outline_vertices.is_empty() {
+                render_pass.set_pipeline(&self.line_render_pipeline);
+                render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+                render_pass.set_vertex_buffer(0, self.dynamic_vertex_buffer.slice(outline_range));
+                let n_vertices = outline_vertices.len() as u32;
+                render_pass.draw(0..n_vertices, 0..1);
+            }
+
+            // Render the crosshair
+            render_pass.set_pipeline(&self.overlay_render_pipeline);
+            render_pass.set_bind_group(0, &self.crosshair_texture_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.dynamic_vertex_buffer.slice(crosshair_range));
+            render_pass.draw(0..crosshair_vertices.len() as u32, 0..1);
+
             self.text_brush.draw(&mut render_pass);
         }
 
@@ -518,7 +692,7 @@ impl<'a> App<'a> {
             last_frame: Instant::now(),
             render_state: None,
             input_state: InputState::new(),
-            game_state: GameState::new(),
+            game_state: GameState::new(world_seed()),
             frame_delta: MovingAverage::new(100),
             update_time: MovingAverage::new(100),
         }
@@ -542,10 +716,9 @@ impl<'a> App<'a> {
             .with_title("Henka")
             .with_inner_size(winit::dpi::PhysicalSize::new(1920, 1080));
         let window = event_loop.create_window(window_attributes).unwrap();
-        self.render_state = Some(RenderState::new(window).await);
+        self.render_state = Some(RenderState::new(window, &self.game_state.atlas).await);
 
         self.game_state.set_window_size(self.get_window_size());
-        self.game_state.generate_voxels();
 
         // Capture cursor on startup
         self.set_cursor_captured(true);
@@ -581,10 +754,12 @@ impl<'a> App<'a> {
                 },
                 None => "n/a".to_string(),
             };
+            let flower_str = self.game_state.get_targeted_flower_description().map_or(String::new(), |description| format!("{} \n", description));
             self.render_state_mut().text_section.text = vec![
                 OwnedText::new(fps_str).with_scale(32.0).with_color([1.0, 1.0, 0.0, 1.0]),
                 OwnedText::new(update_time_str).with_scale(32.0).with_color([1.0, 1.0, 0.0, 1.0]),
                 OwnedText::new(render_time_str).with_scale(32.0).with_color([1.0, 1.0, 0.0, 1.0]),
+                OwnedText::new(flower_str).with_scale(32.0).with_color([1.0, 1.0, 0.0, 1.0]),
             ];
         }
         self.render_state_mut().write_buffers();
@@ -592,9 +767,12 @@ impl<'a> App<'a> {
     }
 
     fn render(&mut self) {
-        let voxel_vertices = self.game_state.get_voxel_vertices();
+        let player_vertices = self.game_state.get_player_vertices();
         let flower_vertices = self.game_state.get_flower_vertices();
-        self.render_state_mut().render(&voxel_vertices, &flower_vertices).unwrap();
+        let outline_vertices = self.game_state.get_target_outline_vertices();
+        let render_state = self.render_state.as_mut().unwrap();
+        render_state.update_voxel_meshes(self.game_state.get_voxel_meshes());
+        render_state.render(&player_vertices, &flower_vertices, &outline_vertices).unwrap();
     }
 }
 
@@ -639,11 +817,13 @@ impl ApplicationHandler for App<'_> {
             },
             WindowEvent::MouseInput {
                 state: ElementState::Pressed,
-                button: MouseButton::Left,
+                button,
                 ..
             } => {
-                // Re-capture cursor on click
-                if !self.input_state.cursor_captured {
+                if self.input_state.cursor_captured {
+                    self.game_state.on_mouse_pressed(button);
+                } else if button == MouseButton::Left {
+                    // Re-capture cursor on click
                     self.set_cursor_captured(true);
                 }
             },
@@ -662,11 +842,22 @@ impl ApplicationHandler for App<'_> {
             _ => (),
         }
         if self.game_state.exit {
+            self.game_state.save_world();
             event_loop.exit();
         }
     }
 }
 
+/// The seed from `HENKA_SEED`, or a random one. It's logged so the same world can be made again.
+fn world_seed() -> u64 {
+    let seed = match std::env::var("HENKA_SEED") {
+        Ok(seed) => seed.parse().expect("HENKA_SEED should be a u64"),
+        Err(_) => rand::random(),
+    };
+    log::info!("World seed: {seed}");
+    seed
+}
+
 pub fn run() {
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
@@ -0,0 +1,93 @@
+#!/usr/bin/env python3
+
+"""
+Generate the per-material block textures.
+
+Each texture is a small tile of seeded noise around a base colour, so the
+output is the same every run. Only the standard library is needed.
+"""
+
+import argparse
+import os
+import random
+import struct
+import zlib
+
+# File constants
+TILE_SIZE = 16
+SEED = 1
+
+# Name, base colour, per-pixel noise amplitude
+TEXTURES = [
+    ('dirt', (134, 96, 67), 18),
+    ('grass_top', (96, 150, 60), 20),
+    ('stone', (125, 125, 128), 16),
+    ('sand', (219, 207, 160), 10),
+    ('water', (50, 90, 190), 8),
+]
+
+# How many rows of grass hang over the top of the dirt on grass_side
+GRASS_SIDE_ROWS = 4
+
+
+def noise_tile(rng, color, amplitude):
+    """
+    Returns TILE_SIZE rows of TILE_SIZE (r, g, b) tuples.
+    """
+    rows = []
+    for _ in range(TILE_SIZE):
+        row = []
+        for _ in range(TILE_SIZE):
+            offset = rng.randint(-amplitude, amplitude)
+            row.append(tuple(max(0, min(255, c + offset)) for c in color))
+        rows.append(row)
+    return rows
+
+
+def write_png(path, rows):
+    """
+    Writes an 8-bit RGB PNG without filtering.
+    """
+    height = len(rows)
+    width = len(rows[0])
+    raw = b''.join(b'\x00' + bytes(c for pixel in row for c in pixel) for row in rows)
+
+    def chunk(kind, data):
+        body = kind + data
+        return struct.pack('>I', len(data)) + body + struct.pack('>I', zlib.crc32(body) & 0xffffffff)
+
+    with open(path, 'wb') as f:
+        f.write(b'\x89PNG\r\n\x1a\n')
+        f.write(chunk(b'IHDR', struct.pack('>IIBBBBB', width, height, 8, 2, 0, 0, 0)))
+        f.write(chunk(b'IDAT', zlib.compress(raw, 9)))
+        f.write(chunk(b'IEND', b''))
+
+
+def main():
+    parser = argparse.ArgumentParser(
+        description='Generate the per-material block textures'
+    )
+    parser.add_argument(
+        'output_dir',
+        help='Directory to write the PNGs to, e.g. textures/materials'
+    )
+
+    args = parser.parse_args()
+    os.makedirs(args.output_dir, exist_ok=True)
+
+    rng = random.Random(SEED)
+    tiles = {}
+    for name, color, amplitude in TEXTURES:
+        tiles[name] = noise_tile(rng, color, amplitude)
+
+    # Dirt with a fringe of grass along the top, for the sides of grassy dirt
+    tiles['grass_side'] = tiles['grass_top'][:GRASS_SIDE_ROWS] + tiles['dirt'][GRASS_SIDE_ROWS:]
+
+    for name, rows in tiles.items():
+        path = os.path.join(args.output_dir, f'{name}.png')
+        write_png(path, rows)
+        print(f"Image saved to: {path}")
+
+
+if __name__ == '__main__':
+    main()